@@ -0,0 +1,235 @@
+use crate::country::Country;
+use crate::model::Address;
+use crate::region;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use strum::IntoEnumIterator;
+
+const MAX_NAME: usize = 50;
+const MAX_LINE: usize = 100;
+const MAX_CITY: usize = 50;
+
+/// What the storefront accepts for an address in a country.
+#[derive(Debug, Default)]
+pub struct AddressRules {
+    /// Pattern the whole postal code has to match, `None` where there are no postal codes.
+    pub postal_code: Option<&'static str>,
+    pub state_required: bool,
+}
+
+impl AddressRules {
+    pub fn of(country: &Country) -> AddressRules {
+        let postal_code = match country {
+            Country::GB => Some(r"(GIR ?0AA|[A-Z]{1,2}\d[A-Z\d]? ?\d[A-Z]{2})"),
+            Country::GG => Some(r"GY\d[\dA-Z]? ?\d[A-Z]{2}"),
+            Country::JE => Some(r"JE\d ?\d[A-Z]{2}"),
+            Country::GI => Some(r"GX11 ?1AA"),
+            Country::IE => Some(r"[A-Z]\d[\dW] ?[A-Z\d]{4}"),
+            Country::US => Some(r"\d{5}(-\d{4})?"),
+            Country::PR => Some(r"00[679]\d{2}(-\d{4})?"),
+            Country::CA => Some(r"[A-Z]\d[A-Z] ?\d[A-Z]\d"),
+            Country::NL => Some(r"\d{4} ?[A-Z]{2}"),
+            Country::PL => Some(r"\d{2}-\d{3}"),
+            Country::PT => Some(r"\d{4}-\d{3}"),
+            Country::CZ | Country::SK | Country::SE | Country::GR => Some(r"\d{3} ?\d{2}"),
+            Country::MT => Some(r"[A-Z]{3} ?\d{2,4}"),
+            Country::LV => Some(r"(LV-)?\d{4}"),
+            Country::LT => Some(r"(LT-)?\d{5}"),
+            Country::MD => Some(r"(MD-?)?\d{4}"),
+            Country::AD => Some(r"AD\d{3}"),
+            Country::AZ => Some(r"(AZ ?)?\d{4}"),
+            Country::IC => Some(r"3[58]\d{3}"),
+            Country::MC => Some(r"980\d{2}"),
+            Country::SM => Some(r"4789\d"),
+            Country::VA => Some(r"00120"),
+            Country::LI => Some(r"94\d{2}"),
+            Country::GL => Some(r"39\d{2}"),
+            Country::IS | Country::FO => Some(r"\d{3}"),
+            Country::AL
+            | Country::AT
+            | Country::BE
+            | Country::BG
+            | Country::CH
+            | Country::CY
+            | Country::DK
+            | Country::HU
+            | Country::LU
+            | Country::MK
+            | Country::NO
+            | Country::SI => Some(r"\d{4}"),
+            Country::BY | Country::RO | Country::RU => Some(r"\d{6}"),
+            Country::BA
+            | Country::HR
+            | Country::EE
+            | Country::FI
+            | Country::FR
+            | Country::DE
+            | Country::IT
+            | Country::KV
+            | Country::ME
+            | Country::RS
+            | Country::ES
+            | Country::TR
+            | Country::UA => Some(r"\d{5}"),
+        };
+
+        AddressRules {
+            postal_code,
+            state_required: matches!(country, Country::US | Country::CA),
+        }
+    }
+}
+
+/// Postal code patterns by country, anchored and case-insensitive, compiled on first use.
+static POSTAL_CODES: LazyLock<HashMap<Country, Regex>> = LazyLock::new(|| {
+    Country::iter()
+        .filter_map(|country| {
+            let pattern = AddressRules::of(&country).postal_code?;
+            let regex = Regex::new(&format!("(?i)^(?:{})$", pattern))
+                .unwrap_or_else(|why| panic!("{:?} postal code pattern: {}", country, why));
+
+            Some((country, regex))
+        })
+        .collect()
+});
+
+/// Every problem with `address`, e.g. `zip="SW1A 1AA" is not a valid Germany postal code`.
+pub fn validate(address: &Address) -> Vec<String> {
+    let country = address.country.data().fps.name;
+    let rules = AddressRules::of(&address.country);
+    let mut problems = Vec::new();
+
+    let mut field = |name: &str, value: &str, max: usize, required: bool| {
+        if required && value.trim().is_empty() {
+            problems.push(format!("{} is required", name));
+        } else if value.chars().count() > max {
+            problems.push(format!("{} is longer than {} characters", name, max));
+        }
+    };
+
+    field("firstName", &address.first_name, MAX_NAME, true);
+    field("lastName", &address.last_name, MAX_NAME, true);
+    field("address1", &address.address1, MAX_LINE, true);
+    field(
+        "address2",
+        address.address2.as_deref().unwrap_or_default(),
+        MAX_LINE,
+        false,
+    );
+    field("city", &address.city, MAX_CITY, true);
+
+    let zip = address.zip.trim();
+    match rules.postal_code {
+        Some(_) if zip.is_empty() => problems.push("zip is required".into()),
+        Some(_) if !POSTAL_CODES[&address.country].is_match(zip) => problems.push(format!(
+            "zip=\"{}\" is not a valid {} postal code",
+            zip, country
+        )),
+        _ => {}
+    }
+
+    let known = |state: &str| {
+        region::catalog(&address.country).is_empty()
+            || region::find(&address.country, state).is_some()
+    };
+
+    match address.state.as_deref().map(str::trim) {
+        None | Some("") if rules.state_required => {
+            problems.push(format!("state is required in {}", country))
+        }
+        Some(state) if !state.is_empty() && !known(state) => {
+            problems.push(format!("state=\"{}\" is not a state of {}", state, country))
+        }
+        _ => {}
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn address(country: &str, zip: &str, state: Option<&str>) -> Address {
+        serde_json::from_value(json!({
+            "firstName": "Jane",
+            "lastName": "Doe",
+            "address1": "1 Test Street",
+            "zip": zip,
+            "city": "Town",
+            "country": country,
+            "state": state,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_addresses() {
+        assert!(validate(&address("GB", "SW1A 1AA", None)).is_empty());
+        assert!(validate(&address("GB", "sw1a1aa", None)).is_empty());
+        assert!(validate(&address("DE", "10115", None)).is_empty());
+        assert!(validate(&address("US", "94103-1234", Some("Calif."))).is_empty());
+        assert!(validate(&address("CA", "K1A 0B1", Some("ON"))).is_empty());
+    }
+
+    #[test]
+    fn compiles_every_postal_code_pattern() {
+        let mut patterns = 0;
+
+        for country in Country::iter() {
+            if let Some(pattern) = AddressRules::of(&country).postal_code {
+                assert!(Regex::new(pattern).is_ok(), "{:?} {}", country, pattern);
+                patterns += 1;
+            }
+        }
+
+        assert_eq!(POSTAL_CODES.len(), patterns);
+    }
+
+    #[test]
+    fn rejects_postal_codes_of_other_countries() {
+        assert_eq!(
+            validate(&address("DE", "SW1A 1AA", None)),
+            vec!["zip=\"SW1A 1AA\" is not a valid Germany postal code"]
+        );
+        assert_eq!(
+            validate(&address("US", "", Some("NY"))),
+            vec!["zip is required"]
+        );
+    }
+
+    #[test]
+    fn requires_known_states() {
+        assert_eq!(
+            validate(&address("US", "10001", None)),
+            vec!["state is required in United States"]
+        );
+        assert_eq!(
+            validate(&address("CA", "K1A 0B1", Some("NY"))),
+            vec!["state=\"NY\" is not a state of Canada"]
+        );
+        assert_eq!(
+            validate(&address("IT", "20121", Some("Lombardia"))),
+            vec!["state=\"Lombardia\" is not a state of Italy"]
+        );
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let mut address = address("US", "1234", Some(""));
+        address.city = " ".into();
+        address.first_name = "J".repeat(51);
+
+        assert_eq!(
+            validate(&address),
+            vec![
+                "firstName is longer than 50 characters",
+                "city is required",
+                "zip=\"1234\" is not a valid United States postal code",
+                "state is required in United States",
+            ]
+        );
+    }
+}
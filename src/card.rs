@@ -0,0 +1,325 @@
+use crate::model::{Card, FPSCreditCard};
+use std::ops::RangeInclusive;
+use std::time::{SystemTime, UNIX_EPOCH};
+use strum::Display;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum CardBrand {
+    Visa,
+    MasterCard,
+    #[strum(serialize = "American Express")]
+    AmericanExpress,
+    Discover,
+    #[strum(serialize = "Diners Club")]
+    DinersClub,
+    #[strum(serialize = "JCB")]
+    Jcb,
+    Maestro,
+    UnionPay,
+}
+
+impl CardBrand {
+    /// Detects the brand from the leading digits of a card number.
+    pub fn detect(number: &str) -> Option<CardBrand> {
+        let digits = number
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>();
+
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let prefix = |len: usize| digits.get(..len).and_then(|p| p.parse::<u32>().ok());
+        let within = |len: usize, low: u32, high: u32| {
+            prefix(len).is_some_and(|p| (low..=high).contains(&p))
+        };
+
+        if within(2, 34, 34) || within(2, 37, 37) {
+            Some(CardBrand::AmericanExpress)
+        } else if within(2, 51, 55) || within(4, 2221, 2720) {
+            Some(CardBrand::MasterCard)
+        } else if within(4, 6011, 6011) || within(3, 644, 649) || within(2, 65, 65) {
+            Some(CardBrand::Discover)
+        } else if within(3, 300, 305) || within(2, 36, 36) || within(2, 38, 39) {
+            Some(CardBrand::DinersClub)
+        } else if within(4, 3528, 3589) {
+            Some(CardBrand::Jcb)
+        } else if within(2, 62, 62) {
+            Some(CardBrand::UnionPay)
+        } else if within(2, 50, 50) || within(2, 56, 58) || within(1, 6, 6) {
+            Some(CardBrand::Maestro)
+        } else if within(1, 4, 4) {
+            Some(CardBrand::Visa)
+        } else {
+            None
+        }
+    }
+
+    /// Number of digits a card number of this brand has.
+    fn lengths(&self) -> RangeInclusive<usize> {
+        match self {
+            CardBrand::Visa => 13..=19,
+            CardBrand::MasterCard => 16..=16,
+            CardBrand::AmericanExpress => 15..=15,
+            CardBrand::DinersClub => 14..=19,
+            CardBrand::Maestro => 12..=19,
+            CardBrand::Discover | CardBrand::Jcb | CardBrand::UnionPay => 16..=19,
+        }
+    }
+
+    fn cvv_length(&self) -> usize {
+        match self {
+            CardBrand::AmericanExpress => 4,
+            _ => 3,
+        }
+    }
+
+    /// Names the storefront uses for this brand in `FPSCreditCard.code`/`description`,
+    /// lowercased and without spaces.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            CardBrand::Visa => &["visa"],
+            CardBrand::MasterCard => &["mastercard", "mc"],
+            CardBrand::AmericanExpress => &["americanexpress", "amex"],
+            CardBrand::Discover => &["discover"],
+            CardBrand::DinersClub => &["dinersclub", "diners"],
+            CardBrand::Jcb => &["jcb"],
+            CardBrand::Maestro => &["maestro"],
+            CardBrand::UnionPay => &["unionpay", "cup"],
+        }
+    }
+
+    pub fn matches(&self, card: &FPSCreditCard) -> bool {
+        let normalize = |value: &str| {
+            value
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '_' && *c != '-')
+                .collect::<String>()
+                .to_lowercase()
+        };
+
+        let code = normalize(&card.code);
+        let description = normalize(&card.description);
+
+        self.aliases()
+            .iter()
+            .any(|alias| code == *alias || description == *alias)
+    }
+}
+
+impl Card {
+    pub fn brand(&self) -> Option<CardBrand> {
+        CardBrand::detect(self.number.expose())
+    }
+
+    /// Checks the card as of `year`/`month` and normalizes it on the way: the number is
+    /// reduced to its digits and a two-digit expiry year is read as 20xx.
+    pub fn validate(&mut self, (year, month): (i64, i64)) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        let digits = self
+            .number
+            .expose()
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .collect::<String>();
+        self.number = digits.into();
+        if self.expiry_year < 100 {
+            self.expiry_year += 2000;
+        }
+
+        match self.brand() {
+            None => problems.push("card.number is not a known card brand".into()),
+            Some(brand) => {
+                let lengths = brand.lengths();
+                let digits = self.number.expose().len();
+                if !lengths.contains(&digits) {
+                    let expected = if lengths.start() == lengths.end() {
+                        lengths.start().to_string()
+                    } else {
+                        format!("{} to {}", lengths.start(), lengths.end())
+                    };
+                    problems.push(format!(
+                        "card.number has {} digits, {} numbers have {}",
+                        digits, brand, expected
+                    ));
+                }
+
+                let cvv = self.cvv.expose();
+                if cvv.len() != brand.cvv_length() || !cvv.chars().all(|c| c.is_ascii_digit()) {
+                    problems.push(format!(
+                        "card.cvv must be {} digits for {}",
+                        brand.cvv_length(),
+                        brand
+                    ));
+                }
+            }
+        }
+
+        if self.brand().is_some() && !luhn(self.number.expose()) {
+            problems.push("card.number fails the Luhn check".into());
+        }
+
+        if !(1..=12).contains(&self.expiry_month) {
+            problems.push(format!(
+                "card.expiryMonth={} is not a month",
+                self.expiry_month
+            ));
+        } else if (self.expiry_year, self.expiry_month) < (year, month) {
+            problems.push(format!(
+                "card expired in {}-{:02}",
+                self.expiry_year, self.expiry_month
+            ));
+        }
+
+        problems
+    }
+}
+
+fn luhn(digits: &str) -> bool {
+    let sum = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(index, digit)| match (index % 2, digit * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => digit,
+        })
+        .sum::<u32>();
+
+    sum % 10 == 0
+}
+
+/// The current year and month in UTC.
+pub fn current_month() -> (i64, i64) {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() / 86_400)
+        .unwrap_or_default();
+
+    month_of(days as i64)
+}
+
+/// Year and month of a day counted from 1970-01-01, see
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn month_of(days: i64) -> (i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_brands() {
+        assert_eq!(
+            CardBrand::detect("4111 1111 1111 1111"),
+            Some(CardBrand::Visa)
+        );
+        assert_eq!(
+            CardBrand::detect("5555555555554444"),
+            Some(CardBrand::MasterCard)
+        );
+        assert_eq!(
+            CardBrand::detect("2223003122003222"),
+            Some(CardBrand::MasterCard)
+        );
+        assert_eq!(
+            CardBrand::detect("378282246310005"),
+            Some(CardBrand::AmericanExpress)
+        );
+        assert_eq!(
+            CardBrand::detect("6011111111111117"),
+            Some(CardBrand::Discover)
+        );
+        assert_eq!(CardBrand::detect("3530111333300000"), Some(CardBrand::Jcb));
+        assert_eq!(CardBrand::detect("1234567812345678"), None);
+        assert_eq!(CardBrand::detect("4111-abcd"), None);
+    }
+
+    #[test]
+    fn matches_storefront_codes() {
+        let card = |code: &str, description: &str| FPSCreditCard {
+            id: "id".into(),
+            code: code.into(),
+            description: description.into(),
+        };
+
+        assert!(CardBrand::MasterCard.matches(&card("MasterCard", "Mastercard")));
+        assert!(CardBrand::AmericanExpress.matches(&card("AMEX", "American Express")));
+        assert!(!CardBrand::Visa.matches(&card("MasterCard", "Mastercard")));
+    }
+
+    fn card(number: &str, cvv: &str, expiry_month: i64, expiry_year: i64) -> Card {
+        Card {
+            number: number.into(),
+            expiry_month,
+            expiry_year,
+            cvv: cvv.into(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_cards() {
+        let mut visa = card("4111 1111 1111 1111", "123", 6, 21);
+        assert!(visa.validate((2021, 6)).is_empty());
+        assert_eq!(visa.number.expose(), "4111111111111111");
+        assert_eq!(visa.expiry_year, 2021);
+        assert_eq!(visa.brand(), Some(CardBrand::Visa));
+
+        let mut amex = card("3782-822463-10005", "1234", 1, 2030);
+        assert!(amex.validate((2021, 6)).is_empty());
+    }
+
+    #[test]
+    fn reports_card_problems() {
+        assert_eq!(
+            card("4111111111111112", "123", 12, 2030).validate((2021, 6)),
+            vec!["card.number fails the Luhn check"]
+        );
+        assert_eq!(
+            card("378282246310005", "123", 12, 2030).validate((2021, 6)),
+            vec!["card.cvv must be 4 digits for American Express"]
+        );
+        assert_eq!(
+            card("555555555555444", "123", 5, 2021).validate((2021, 6)),
+            vec![
+                "card.number has 15 digits, MasterCard numbers have 16",
+                "card.number fails the Luhn check",
+                "card expired in 2021-05",
+            ]
+        );
+        assert_eq!(
+            card("1234567812345670", "123", 13, 2030).validate((2021, 6)),
+            vec![
+                "card.number is not a known card brand",
+                "card.expiryMonth=13 is not a month",
+            ]
+        );
+    }
+
+    #[test]
+    fn computes_calendar_months() {
+        assert_eq!(month_of(0), (1970, 1));
+        assert_eq!(month_of(11_016), (2000, 2));
+        assert_eq!(month_of(19_000), (2022, 1));
+        assert_eq!(month_of(20_000), (2024, 10));
+    }
+}
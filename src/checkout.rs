@@ -0,0 +1,384 @@
+use crate::model::{FPSFinalize, FPSOrder};
+use crate::Error;
+use serde::Deserialize;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Where a task is in the checkout. A task that stops on a transient error keeps its last
+/// successful state, so starting it again resumes from there instead of placing a new order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckoutState {
+    Started,
+    SessionCreated,
+    OrderCreated {
+        order: FPSOrder,
+    },
+    AddressPatched {
+        order: FPSOrder,
+    },
+    /// Payment was sent and its outcome is not known yet, it is read from the order. A task
+    /// resumed in this state never pays again.
+    PaymentSubmitted {
+        order: i64,
+    },
+    Confirmed {
+        order: i64,
+        reference: String,
+    },
+    Declined {
+        order: i64,
+        reason: String,
+    },
+    /// The payment needs 3-D Secure or another redirect, which has to be completed by hand.
+    ActionRequired {
+        order: i64,
+        url: String,
+    },
+    DryRunCompleted {
+        order: i64,
+    },
+    /// The purchase or decline limits are reached, the task stops buying.
+    Done {
+        purchases: u32,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+impl fmt::Display for CheckoutState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckoutState::Started => write!(f, "started"),
+            CheckoutState::SessionCreated => write!(f, "session_created"),
+            CheckoutState::OrderCreated { order } => write!(f, "order_created order={}", order.id),
+            CheckoutState::AddressPatched { order } => {
+                write!(f, "address_patched order={}", order.id)
+            }
+            CheckoutState::PaymentSubmitted { order } => {
+                write!(f, "payment_submitted order={}", order)
+            }
+            CheckoutState::Confirmed { order, reference } => {
+                write!(f, "confirmed order={} reference={}", order, reference)
+            }
+            CheckoutState::Declined { order, reason } => {
+                write!(f, "declined order={} reason=\"{}\"", order, reason)
+            }
+            CheckoutState::ActionRequired { order, url } => {
+                write!(f, "action_required order={} url={}", order, url)
+            }
+            CheckoutState::DryRunCompleted { order } => {
+                write!(f, "dry_run_completed order={}", order)
+            }
+            CheckoutState::Done { purchases } => write!(f, "done purchases={}", purchases),
+            CheckoutState::Failed { reason } => write!(f, "failed reason=\"{}\"", reason),
+        }
+    }
+}
+
+/// What `/finalize` made of a payment.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FinalizeOutcome {
+    Approved { reference: String },
+    Declined { reason: String },
+    RequiresAction { url: String },
+    Pending { reference: Option<String> },
+}
+
+impl From<FPSFinalize> for FinalizeOutcome {
+    fn from(finalize: FPSFinalize) -> FinalizeOutcome {
+        if !finalize.errors.is_empty() {
+            let reason = finalize
+                .errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+
+            return FinalizeOutcome::Declined { reason };
+        }
+
+        let reference = finalize.checkout_order.map(|order| order.order_id);
+
+        // Only a refusal is a decline. A payment that errored or was cancelled may still
+        // have been charged, so it is left pending for the order to tell.
+        match (finalize.payment_status.as_deref(), finalize.redirect_url) {
+            (Some(status @ "Refused"), _) | (Some(status @ "Declined"), _) => {
+                FinalizeOutcome::Declined {
+                    reason: finalize.refusal_reason.unwrap_or_else(|| status.into()),
+                }
+            }
+            (_, Some(url)) => FinalizeOutcome::RequiresAction { url },
+            (Some("Pending"), _)
+            | (Some("Received"), _)
+            | (Some("Error"), _)
+            | (Some("Cancelled"), _) => FinalizeOutcome::Pending { reference },
+            (_, None) => match reference {
+                Some(reference) => FinalizeOutcome::Approved { reference },
+                None => FinalizeOutcome::Pending { reference },
+            },
+        }
+    }
+}
+
+/// What the order says about a payment `/finalize` left pending. An `orderStatus` other
+/// than 0 reports a problem with the order. `checkoutOrder.status` is 0 while the order is
+/// open and 1 once it is placed, any other status means it was cancelled.
+impl From<FPSOrder> for FinalizeOutcome {
+    fn from(order: FPSOrder) -> FinalizeOutcome {
+        let checkout = order.checkout_order;
+
+        match (order.order_status, checkout.status) {
+            (0, 0) => FinalizeOutcome::Pending {
+                reference: Some(checkout.order_id),
+            },
+            (0, 1) => FinalizeOutcome::Approved {
+                reference: checkout.order_id,
+            },
+            (0, status) => FinalizeOutcome::Declined {
+                reason: format!("checkout order status {}", status),
+            },
+            (status, _) => FinalizeOutcome::Declined {
+                reason: format!("order status {}", status),
+            },
+        }
+    }
+}
+
+impl fmt::Display for FinalizeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FinalizeOutcome::Approved { reference } => {
+                write!(f, "approved reference={}", reference)
+            }
+            FinalizeOutcome::Declined { reason } => write!(f, "declined reason=\"{}\"", reason),
+            FinalizeOutcome::RequiresAction { url } => write!(f, "requires_action url={}", url),
+            FinalizeOutcome::Pending { reference } => match reference {
+                Some(reference) => write!(f, "pending reference={}", reference),
+                None => write!(f, "pending"),
+            },
+        }
+    }
+}
+
+/// A payment outcome with the shipping services the order was placed with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PurchaseOutcome {
+    pub outcome: FinalizeOutcome,
+    /// Services chosen for the merchant groups, comma separated. `None` when the order
+    /// offered no shipping options to choose from.
+    pub shipping: Option<String>,
+}
+
+impl fmt::Display for PurchaseOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.shipping {
+            Some(shipping) => write!(f, "{} shipping=\"{}\"", self.outcome, shipping),
+            None => write!(f, "{}", self.outcome),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    pub attempts: usize,
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+impl RetryPolicy {
+    const fn new(attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            delay_ms: 0,
+        }
+    }
+
+    /// Runs `action` until it succeeds, fails with a non-transient error or runs out of attempts.
+    pub async fn run<T, F, Fut>(&self, mut action: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match action().await {
+                Ok(value) => return Ok(value),
+                Err(why) if why.is_transient() && attempt < self.attempts => {
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(self.delay_ms)).await;
+                }
+                Err(why) => return Err(why),
+            }
+        }
+    }
+}
+
+/// Retry policy for each checkout transition. Payment is sent once and never retried, a
+/// payment that failed on the way is confirmed from its order instead.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CheckoutPolicy {
+    pub session: RetryPolicy,
+    pub order: RetryPolicy,
+    pub address: RetryPolicy,
+    pub confirmation: RetryPolicy,
+    /// Restarts of the whole task from its last state once a transition runs out of attempts.
+    pub resume: RetryPolicy,
+}
+
+impl Default for CheckoutPolicy {
+    fn default() -> CheckoutPolicy {
+        CheckoutPolicy {
+            session: RetryPolicy::new(6),
+            order: RetryPolicy::new(11),
+            address: RetryPolicy::new(11),
+            confirmation: RetryPolicy::new(3),
+            resume: RetryPolicy {
+                attempts: 3,
+                delay_ms: 5000,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PurchaseLimits {
+    /// Units each profile may buy.
+    pub per_profile: u32,
+    /// Units all profiles of a task may buy together.
+    pub total: Option<u32>,
+    /// Declined payments after which a profile stops buying, so a bad card is not tried on
+    /// every monitor update.
+    pub declines: u32,
+}
+
+impl Default for PurchaseLimits {
+    fn default() -> PurchaseLimits {
+        PurchaseLimits {
+            per_profile: 1,
+            total: None,
+            declines: 1,
+        }
+    }
+}
+
+/// Units claimed across the profiles of one task. A task claims a unit before it submits
+/// payment and gives it back if the payment is declined.
+#[derive(Debug, Clone, Default)]
+pub struct PurchaseCounter {
+    limit: Option<u32>,
+    claimed: Arc<AtomicU32>,
+}
+
+impl PurchaseCounter {
+    pub fn new(limit: Option<u32>) -> PurchaseCounter {
+        PurchaseCounter {
+            limit,
+            claimed: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    pub fn has_capacity(&self) -> bool {
+        match self.limit {
+            Some(limit) => self.claimed.load(Ordering::SeqCst) < limit,
+            None => true,
+        }
+    }
+
+    pub fn claim(&self) -> bool {
+        self.claimed
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |claimed| {
+                match self.limit {
+                    Some(limit) if claimed >= limit => None,
+                    _ => Some(claimed + 1),
+                }
+            })
+            .is_ok()
+    }
+
+    pub fn release(&self) {
+        self.claimed.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(json: &str) -> FinalizeOutcome {
+        serde_json::from_str::<FPSFinalize>(json).unwrap().into()
+    }
+
+    #[test]
+    fn parses_finalize_outcomes() {
+        assert_eq!(
+            outcome(r#"{"checkoutOrder": {"orderId": "3XK9QF"}, "paymentStatus": "Authorised"}"#),
+            FinalizeOutcome::Approved {
+                reference: "3XK9QF".into()
+            }
+        );
+        assert_eq!(
+            outcome(r#"{"paymentStatus": "Refused", "refusalReason": "Not enough balance"}"#),
+            FinalizeOutcome::Declined {
+                reason: "Not enough balance".into()
+            }
+        );
+        assert_eq!(
+            outcome(r#"{"errors": [{"code": "PaymentDeclined", "message": "Card declined"}]}"#),
+            FinalizeOutcome::Declined {
+                reason: "Card declined".into()
+            }
+        );
+        assert_eq!(
+            outcome(
+                r#"{"paymentStatus": "RedirectShopper", "redirectUrl": "https://3ds.test/acs"}"#
+            ),
+            FinalizeOutcome::RequiresAction {
+                url: "https://3ds.test/acs".into()
+            }
+        );
+        assert_eq!(
+            outcome(r#"{"checkoutOrder": {"orderId": "3XK9QF"}, "paymentStatus": "Pending"}"#),
+            FinalizeOutcome::Pending {
+                reference: Some("3XK9QF".into())
+            }
+        );
+        assert_eq!(
+            outcome(r#"{"checkoutOrder": {"orderId": "3XK9QF"}, "paymentStatus": "Error"}"#),
+            FinalizeOutcome::Pending {
+                reference: Some("3XK9QF".into())
+            }
+        );
+        assert_eq!(outcome("{}"), FinalizeOutcome::Pending { reference: None });
+    }
+
+    #[test]
+    fn counter_claims_up_to_limit() {
+        let counter = PurchaseCounter::new(Some(2));
+        let shared = counter.clone();
+
+        assert!(counter.claim());
+        assert!(shared.claim());
+        assert!(!counter.claim());
+        assert!(!shared.has_capacity());
+
+        shared.release();
+        assert!(counter.has_capacity());
+        assert!(counter.claim());
+    }
+
+    #[test]
+    fn unlimited_counter_always_claims() {
+        let counter = PurchaseCounter::default();
+
+        assert!((0..100).all(|_| counter.claim()));
+        assert!(counter.has_capacity());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::country::Country;
+use crate::model::FPSCardPaymentIntent;
+use crate::model::FPSCreateOrder;
+use crate::model::FPSCreatedIntent;
+use crate::model::FPSFinalize;
+use crate::model::FPSIntentFinalize;
+use crate::model::FPSOrder;
+use crate::model::FPSPatchAddress;
+use crate::model::FPSProduct;
+use crate::model::FPSShippingOption;
+use crate::model::FPSUser;
+use crate::payment::PaymentForm;
+use crate::storefront::Storefront;
+use crate::Error;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, ORIGIN, REFERER, USER_AGENT};
+use reqwest::Client;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strum::AsStaticRef;
+
+/// Typed client for the storefront API of one storefront and country.
+#[derive(Debug)]
+pub struct FpsClient {
+    client: Client,
+    payment_client: Client,
+    storefront: Storefront,
+    country: Country,
+    base_url: Url,
+}
+
+impl FpsClient {
+    pub fn new(storefront: Storefront, country: Country) -> Result<FpsClient, Error> {
+        let base_url = storefront.country_url(&country);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ORIGIN,
+            HeaderValue::from_str(storefront.base_url.origin().ascii_serialization().as_str())?,
+        );
+        headers.insert(REFERER, HeaderValue::from_str(base_url.as_str())?);
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0",
+            ),
+        );
+        headers.insert(
+            ACCEPT_LANGUAGE,
+            HeaderValue::from_static(country.accept_language()),
+        );
+        headers.insert("FF-Country", HeaderValue::from_static(country.as_static()));
+        headers.insert(
+            "FF-Currency",
+            HeaderValue::from_static(country.fps_currency()),
+        );
+
+        let client = Client::builder()
+            .use_rustls_tls()
+            .gzip(true)
+            .timeout(Duration::from_millis(storefront.timeout_ms))
+            .default_headers(headers)
+            .cookie_store(true)
+            .user_agent("Mozilla/5.0 (iPhone; CPU iPhone OS 14_4_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1")
+            .build()?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_static(
+                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0",
+            ),
+        );
+        headers.insert("True-Client-IP", HeaderValue::from_static("127.0.0.1"));
+
+        let payment_client = Client::builder()
+            .use_rustls_tls()
+            .gzip(true)
+            .timeout(Duration::from_millis(storefront.timeout_ms))
+            .cookie_store(true)
+            .default_headers(headers)
+            .user_agent("Mozilla/5.0 (iPhone; CPU iPhone OS 14_4_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1")
+            .build()?;
+
+        Ok(FpsClient {
+            client,
+            payment_client,
+            storefront,
+            country,
+            base_url,
+        })
+    }
+
+    pub fn storefront(&self) -> &Storefront {
+        &self.storefront
+    }
+
+    pub async fn get_product(&self, product: &str) -> Result<FPSProduct, Error> {
+        let since_the_epoch = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let query = format!("ts={}", since_the_epoch.as_millis());
+
+        let mut url = self.api_url(&["products", product])?;
+        url.set_query(Some(&query));
+
+        let response = self.client.get(url).send().await?.error_for_status()?;
+
+        json(response).await
+    }
+
+    pub async fn me(&self) -> Result<FPSUser, Error> {
+        let url = self.api_url(&["users", "me"])?;
+        let response = self.client.get(url).send().await?.error_for_status()?;
+
+        json(response).await
+    }
+
+    pub async fn create_order(&self, order: &FPSCreateOrder<'_>) -> Result<FPSOrder, Error> {
+        let url = self.api_url(&["checkout", "v1", "orders"])?;
+        let response = self
+            .client
+            .post(url)
+            .json(order)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        json(response).await
+    }
+
+    pub async fn get_order(&self, order: i64) -> Result<FPSOrder, Error> {
+        let url = self.order_url(order, None)?;
+        let response = self.client.get(url).send().await?.error_for_status()?;
+
+        json(response).await
+    }
+
+    pub async fn patch_order_address(
+        &self,
+        order: i64,
+        address: &FPSPatchAddress<'_>,
+    ) -> Result<FPSOrder, Error> {
+        let url = self.order_url(order, None)?;
+        let response = self
+            .client
+            .patch(url)
+            .json(address)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        json(response).await
+    }
+
+    pub async fn set_order_shipping(
+        &self,
+        order: i64,
+        options: &[&FPSShippingOption],
+    ) -> Result<FPSOrder, Error> {
+        let url = self.order_url(order, Some("shippingOptions"))?;
+        let response = self
+            .client
+            .put(url)
+            .json(options)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        json(response).await
+    }
+
+    /// Client errors that carry an `errors` body are returned as a finalize response, since
+    /// that is how the storefront reports a declined payment. Any other failure, including a
+    /// success body that does not parse, is an error: the payment may have gone through.
+    pub async fn finalize_order(
+        &self,
+        order: i64,
+        payment: &FPSCardPaymentIntent<'_>,
+    ) -> Result<FPSFinalize, Error> {
+        self.finalize(order, payment).await
+    }
+
+    pub async fn finalize_order_intent(
+        &self,
+        order: i64,
+        intent: &FPSIntentFinalize<'_>,
+    ) -> Result<FPSFinalize, Error> {
+        self.finalize(order, intent).await
+    }
+
+    pub async fn fetch_payment_form(&self, payment_intent: &str) -> Result<PaymentForm, Error> {
+        let url = Url::parse_with_params(
+            self.payment_gateway()?.as_str(),
+            &[
+                ("paymentIntentId", payment_intent),
+                ("staticName", &self.storefront.static_name),
+                ("folderName", &self.storefront.folder_name),
+                ("locale", self.country.accept_language()),
+            ],
+        )?;
+
+        let response = self
+            .payment_client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        PaymentForm::parse(&response.text().await?)
+    }
+
+    pub async fn create_payment_instrument(
+        &self,
+        form: &PaymentForm,
+        card: &FPSCardPaymentIntent<'_>,
+    ) -> Result<FPSCreatedIntent, Error> {
+        let gateway = self.payment_gateway()?;
+        let mut url = gateway.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::Unknown(format!("invalid payment gateway {}", gateway)))?
+            .pop_if_empty()
+            .push("instruments");
+        url.query_pairs_mut()
+            .append_pair("sessionId", &form.session_id);
+
+        let response = self
+            .payment_client
+            .post(url)
+            .header("X-CSRF-TOKEN", &form.csrf)
+            .json(card)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        json(response).await
+    }
+
+    fn payment_gateway(&self) -> Result<&Url, Error> {
+        self.storefront.payment_gateway.as_ref().ok_or_else(|| {
+            Error::PaymentForm(format!("{} has no payment gateway", self.storefront.name))
+        })
+    }
+
+    async fn finalize<T: Serialize>(&self, order: i64, body: &T) -> Result<FPSFinalize, Error> {
+        let url = self.order_url(order, Some("finalize"))?;
+        let response = self.client.post(url).json(body).send().await?;
+
+        if response.status().is_client_error() {
+            let error = response.error_for_status_ref().err();
+            let body = response.bytes().await?;
+
+            return match (serde_json::from_slice::<FPSFinalize>(&body), error) {
+                (Ok(finalize), _) if !finalize.errors.is_empty() => Ok(finalize),
+                (_, Some(error)) => Err(error.into()),
+                (result, None) => Ok(result?),
+            };
+        }
+
+        json(response.error_for_status()?).await
+    }
+
+    fn order_url(&self, order: i64, action: Option<&str>) -> Result<Url, Error> {
+        let order = order.to_string();
+        let mut segments = vec!["checkout", "v1", "orders", order.as_str()];
+        segments.extend(action);
+
+        self.api_url(&segments)
+    }
+
+    fn api_url(&self, segments: &[&str]) -> Result<Url, Error> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::Unknown(format!("invalid storefront url {}", self.base_url)))?
+            .pop_if_empty()
+            .push("api")
+            .extend(segments);
+
+        Ok(url)
+    }
+}
+
+async fn json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Error> {
+    let body = response.bytes().await?;
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockServer};
+    use crate::model::FPSItem;
+    use reqwest::Method;
+
+    fn client(country: Country) -> FpsClient {
+        let storefront = Storefront::builtin("emiliopucci").unwrap();
+        FpsClient::new(storefront, country).unwrap()
+    }
+
+    #[test]
+    fn product_url_is_under_country_locale() {
+        let url = client(Country::GB)
+            .api_url(&["products", "16472289"])
+            .unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://www.emiliopucci.com/en-gb/api/products/16472289"
+        );
+    }
+
+    #[test]
+    fn order_urls_share_one_prefix() {
+        let client = client(Country::IT);
+
+        assert_eq!(
+            client.order_url(42, None).unwrap().as_str(),
+            "https://www.emiliopucci.com/it-it/api/checkout/v1/orders/42"
+        );
+        assert_eq!(
+            client.order_url(42, Some("finalize")).unwrap().as_str(),
+            "https://www.emiliopucci.com/it-it/api/checkout/v1/orders/42/finalize"
+        );
+    }
+
+    #[test]
+    fn product_id_is_escaped() {
+        let url = client(Country::US)
+            .api_url(&["products", "1/../2"])
+            .unwrap();
+
+        assert_eq!(
+            url.as_str(),
+            "https://www.emiliopucci.com/en-us/api/products/1%2F..%2F2"
+        );
+    }
+
+    #[tokio::test]
+    async fn parses_mock_product() {
+        let server = MockServer::start().await;
+        let client = FpsClient::new(server.storefront(), Country::GB).unwrap();
+
+        let product = client.get_product(mock::PRODUCT_ID).await.unwrap();
+
+        assert_eq!(product.result.id, 16472289);
+        assert_eq!(product.result.variants.len(), 2);
+        assert_eq!(server.requests()[0].path, "products/16472289");
+    }
+
+    #[tokio::test]
+    async fn creates_and_fetches_order() {
+        let server = MockServer::start().await;
+        let client = FpsClient::new(server.storefront(), Country::GB).unwrap();
+
+        let created = client
+            .create_order(&FPSCreateOrder {
+                guest_user_email: "jane@example.com",
+                use_payment_intent: false,
+                shipping_mode: "byMerchant",
+                items: vec![FPSItem {
+                    merchant_id: 11554,
+                    product_id: 16472289,
+                    quantity: 1,
+                    variant_id: "b3f4a2de-0c3e-4f1a-9a53-7d0e3b0a1c01".into(),
+                }],
+            })
+            .await
+            .unwrap();
+        let fetched = client.get_order(created.id).await.unwrap();
+
+        assert_eq!(created.id, 1);
+        assert_eq!(fetched.checkout_order.currency, "GBP");
+
+        let request = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(request.body["items"][0]["productId"], 16472289);
+        assert!(client.get_order(2).await.is_err());
+    }
+}
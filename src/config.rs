@@ -0,0 +1,257 @@
+//! Reads `Config` from JSON, TOML or YAML, picked by file extension. `${NAME}` in any
+//! string is replaced with the environment variable `NAME` before the config is parsed,
+//! and `$${` writes a literal `${`. Numeric fields that are commonly kept in the
+//! environment, such as `expiryMonth`, also accept a string, so `"${EXPIRY_MONTH}"` works.
+
+use crate::model::Config;
+use crate::Error;
+use regex::{Captures, Regex};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const DEFAULT_PATH: &str = "config.json";
+
+/// The config path given with `--config`, else `CONFIG_PATH`, else `config.json`.
+pub fn path(flag: Option<String>) -> PathBuf {
+    flag.or_else(|| std::env::var("CONFIG_PATH").ok())
+        .unwrap_or_else(|| DEFAULT_PATH.into())
+        .into()
+}
+
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+pub fn load(path: &Path) -> Result<Config, Error> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    let format = match extension.as_deref() {
+        Some("json") | None => Format::Json,
+        Some("toml") => Format::Toml,
+        Some("yaml") | Some("yml") => Format::Yaml,
+        Some(extension) => {
+            return Err(Error::InvalidConfig(format!(
+                "unsupported config format .{}",
+                extension
+            )))
+        }
+    };
+
+    let text = fs::read_to_string(path)?;
+    let mut value: Value = match format {
+        Format::Json => serde_json::from_str(&text)?,
+        Format::Toml => toml::from_str(&text)?,
+        Format::Yaml => serde_yaml::from_str(&text)?,
+    };
+
+    interpolate(&mut value, |name| std::env::var(name).ok())?;
+
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Reads a number written as a number or as a string, for fields filled from the
+/// environment.
+pub fn number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number<T> {
+        Number(T),
+        Text(String),
+    }
+
+    match Number::deserialize(deserializer)? {
+        Number::Number(number) => Ok(number),
+        Number::Text(text) => text
+            .trim()
+            .parse()
+            .map_err(|why| de::Error::custom(format!("`{}` is not a number: {}", text, why))),
+    }
+}
+
+/// Replaces `${NAME}` in every string of `value` with `env(NAME)` and `$${` with `${`,
+/// reporting every variable that is not set.
+fn interpolate<F>(value: &mut Value, env: F) -> Result<(), Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let pattern = Regex::new(r"\$\$\{|\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap();
+    let mut missing = Vec::new();
+
+    substitute(value, &pattern, &env, &mut missing);
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        missing.sort();
+        missing.dedup();
+        Err(Error::InvalidConfig(format!(
+            "environment variables not set: {}",
+            missing.join(", ")
+        )))
+    }
+}
+
+fn substitute<F>(value: &mut Value, pattern: &Regex, env: &F, missing: &mut Vec<String>)
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match value {
+        Value::String(text) => {
+            let replaced = pattern.replace_all(text, |captures: &Captures| match captures.get(1) {
+                Some(name) => env(name.as_str()).unwrap_or_else(|| {
+                    missing.push(name.as_str().to_string());
+                    String::new()
+                }),
+                None => "${".to_string(),
+            });
+            *text = replaced.into_owned();
+        }
+        Value::Array(values) => {
+            for value in values {
+                substitute(value, pattern, env, missing);
+            }
+        }
+        Value::Object(map) => {
+            for value in map.values_mut() {
+                substitute(value, pattern, env, missing);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Card;
+    use serde_json::json;
+
+    #[test]
+    fn interpolates_nested_strings() {
+        let mut value = json!({
+            "card": { "cvv": "${CVV}", "number": "4111${REST}" },
+            "tags": ["${CVV}-${CVV}", "plain $HOME"],
+            "expiryMonth": 12,
+        });
+        let env = |name: &str| match name {
+            "CVV" => Some("123".to_string()),
+            "REST" => Some("111111111111".to_string()),
+            _ => None,
+        };
+
+        interpolate(&mut value, env).unwrap();
+
+        assert_eq!(
+            value,
+            json!({
+                "card": { "cvv": "123", "number": "4111111111111111" },
+                "tags": ["123-123", "plain $HOME"],
+                "expiryMonth": 12,
+            })
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_references() {
+        let mut value = json!({ "note": "$${HOME} is ${HOME}, $$ stays" });
+
+        interpolate(&mut value, |_| Some("/root".to_string())).unwrap();
+
+        assert_eq!(value, json!({ "note": "${HOME} is /root, $$ stays" }));
+    }
+
+    #[test]
+    fn reports_every_missing_variable() {
+        let mut value = json!(["${B}", { "a": "${A} ${B}" }]);
+
+        assert_eq!(
+            interpolate(&mut value, |_| None).unwrap_err().to_string(),
+            "invalid_config=environment variables not set: A, B"
+        );
+    }
+
+    #[test]
+    fn loads_every_format() {
+        std::env::set_var("CONFIG_TEST_PRODUCT", "16472289");
+        let directory = std::env::temp_dir();
+        let files = [
+            (
+                "json",
+                r#"{ "dryRun": true, "tasks": [{ "product": "${CONFIG_TEST_PRODUCT}" }] }"#,
+            ),
+            (
+                "toml",
+                "dryRun = true\n[[tasks]]\nproduct = \"${CONFIG_TEST_PRODUCT}\"\n",
+            ),
+            (
+                "yaml",
+                "dryRun: true\ntasks:\n  - product: \"${CONFIG_TEST_PRODUCT}\"\n",
+            ),
+        ];
+
+        for (extension, text) in &files {
+            let path = directory.join(format!("config-{}.{}", std::process::id(), extension));
+            fs::write(&path, text).unwrap();
+
+            let config = load(&path);
+            fs::remove_file(&path).unwrap();
+
+            let config = config.unwrap();
+            assert!(config.dry_run, "{}", extension);
+            assert_eq!(config.tasks[0].product, "16472289", "{}", extension);
+        }
+    }
+
+    #[test]
+    fn interpolates_numeric_fields() {
+        std::env::set_var("CONFIG_TEST_POLL_INTERVAL", "250");
+        let path = std::env::temp_dir().join(format!("config-numbers-{}.yaml", std::process::id()));
+        fs::write(
+            &path,
+            "tasks:\n  - product: \"16472289\"\n    pollIntervalMs: \"${CONFIG_TEST_POLL_INTERVAL}\"\n",
+        )
+        .unwrap();
+
+        let config = load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(config.unwrap().tasks[0].poll_interval_ms, 250);
+
+        let card = |month: Value| {
+            serde_json::from_value::<Card>(json!({
+                "number": "4111111111111111",
+                "expiryMonth": month,
+                "expiryYear": "2030",
+                "cvv": "123",
+            }))
+        };
+        assert_eq!(card(json!(" 7 ")).unwrap().expiry_month, 7);
+        assert_eq!(card(json!(7)).unwrap().expiry_year, 2030);
+        assert!(card(json!("July"))
+            .unwrap_err()
+            .to_string()
+            .contains("`July` is not a number"));
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert_eq!(
+            load(Path::new("config.ini")).unwrap_err().to_string(),
+            "invalid_config=unsupported config format .ini"
+        );
+    }
+}
@@ -1,8 +1,13 @@
 use crate::model::FPSCountry;
-use serde::Deserialize;
-use strum::AsStaticStr;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::Serialize;
+use std::str::FromStr;
+use strum::{AsStaticStr, EnumIter, EnumString};
 
-#[derive(Debug, AsStaticStr, Deserialize, PartialEq, Eq, Hash, Clone)]
+/// Destination country by ISO 3166-1 alpha-2 code. `config.json` may spell it in any case,
+/// `"GB"` and `"gb"` are the same country.
+#[derive(Debug, AsStaticStr, EnumIter, EnumString, Serialize, PartialEq, Eq, Hash, Clone)]
+#[strum(ascii_case_insensitive)]
 pub enum Country {
     AL,
     AD,
@@ -63,431 +68,259 @@ pub enum Country {
     US,
 }
 
+/// Everything the storefront needs to know about a destination.
+#[derive(Debug)]
+pub struct CountryData {
+    pub country: Country,
+    pub fps: FPSCountry,
+    /// ISO 4217 code sent as `FF-Currency`.
+    pub currency: &'static str,
+    /// BCP 47 tag sent as `Accept-Language` and as the payment form locale.
+    pub language: &'static str,
+    /// Storefront path segment, e.g. `en-gb` in `/en-gb/api/...`.
+    pub locale: &'static str,
+    /// International calling code without the `+`.
+    pub calling_code: &'static str,
+}
+
+/// Country, FPS id, FPS name, currency, Accept-Language, storefront locale, calling code.
+///
+/// This is not every destination the platform ships to, only the countries of the original
+/// per-country mappings. Others are added once their id and name are read from a storefront's
+/// country payload: a guessed id addresses the order to another country.
+#[rustfmt::skip]
+const COUNTRIES: &[CountryData] = &[
+    entry(Country::AL, 2, "Albania", "USD", "sq-AL", "en-al", "355"),
+    entry(Country::AD, 307, "Andorra", "EUR", "en-GB", "en-ad", "376"),
+    entry(Country::AT, 13, "Austria", "EUR", "de-AT", "en-at", "43"),
+    entry(Country::AZ, 14, "Azerbaijan", "USD", "az-Latn-AZ", "en-az", "994"),
+    entry(Country::BY, 19, "Belarus", "USD", "be-BY", "en-by", "375"),
+    entry(Country::BE, 20, "Belgium", "EUR", "fr-BE", "en-be", "32"),
+    entry(Country::BA, 304, "Bosnia and Herzegovina", "EUR", "en-GB", "en-ba", "387"),
+    entry(Country::BG, 31, "Bulgaria", "EUR", "bg-BG", "en-bg", "359"),
+    entry(Country::HR, 51, "Croatia", "EUR", "hr-HR", "en-hr", "385"),
+    entry(Country::CY, 52, "Cyprus", "EUR", "en-GB", "en-cy", "357"),
+    entry(Country::CZ, 53, "Czech Republic", "EUR", "cs-CZ", "en-cz", "420"),
+    entry(Country::DK, 54, "Denmark", "DKK", "da-DK", "en-dk", "45"),
+    entry(Country::EE, 63, "Estonia", "EUR", "et-EE", "en-ee", "372"),
+    entry(Country::FO, 65, "Faeroe Islands", "USD", "en-GB", "en-fo", "298"),
+    entry(Country::FI, 69, "Finland", "EUR", "fi-FI", "en-fi", "358"),
+    entry(Country::FR, 70, "France", "EUR", "fr-FR", "en-fr", "33"),
+    entry(Country::DE, 77, "Germany", "EUR", "de-DE", "en-de", "49"),
+    entry(Country::GI, 79, "Gibraltar", "GBP", "en-GB", "en-gi", "350"),
+    entry(Country::GR, 80, "Greece", "EUR", "el-GR", "en-gr", "30"),
+    entry(Country::GL, 81, "Greenland", "USD", "kl-GL", "en-gl", "299"),
+    entry(Country::GG, 86, "Guernsey, C.I.", "GBP", "en-GB", "en-gg", "44"),
+    entry(Country::HU, 94, "Hungary", "EUR", "hu-HU", "en-hu", "36"),
+    entry(Country::IS, 95, "Iceland", "USD", "is-IS", "en-is", "354"),
+    entry(Country::IE, 98, "Ireland", "EUR", "en-IE", "en-ie", "353"),
+    entry(Country::IT, 101, "Italy", "EUR", "it-IT", "it-it", "39"),
+    entry(Country::JE, 104, "Jersey, C.I.", "GBP", "en-GB", "en-je", "44"),
+    entry(Country::KV, 311, "Kosovo", "USD", "en-GB", "en-kv", "383"),
+    entry(Country::LV, 113, "Latvia", "EUR", "lv-LV", "en-lv", "371"),
+    entry(Country::LI, 117, "Liechtenstein", "CHF", "de-LI", "en-li", "423"),
+    entry(Country::LT, 118, "Lithuania", "EUR", "lt-LT", "en-lt", "370"),
+    entry(Country::LU, 119, "Luxembourg", "EUR", "fr-LU", "en-lu", "352"),
+    entry(Country::MT, 126, "Malta", "EUR", "mt-MT", "en-mt", "356"),
+    entry(Country::MD, 133, "Moldova, Republic of", "USD", "en-GB", "en-md", "373"),
+    entry(Country::MC, 134, "Monaco", "EUR", "fr-MC", "en-mc", "377"),
+    entry(Country::ME, 302, "Montenegro", "EUR", "en-GB", "en-me", "382"),
+    entry(Country::NL, 144, "Netherlands", "EUR", "nl-NL", "en-nl", "31"),
+    entry(Country::MK, 303, "North Macedonia", "EUR", "en-GB", "en-mk", "389"),
+    entry(Country::NO, 153, "Norway", "USD", "nb-NO", "en-no", "47"),
+    entry(Country::PL, 164, "Poland", "PLN", "en-US", "en-pl", "48"),
+    entry(Country::PT, 165, "Portugal", "EUR", "pt-PT", "en-pt", "351"),
+    entry(Country::RO, 169, "Romania", "EUR", "ro-RO", "en-ro", "40"),
+    entry(Country::RU, 170, "Russian Federation", "RUB", "ru-RU", "en-ru", "7"),
+    entry(Country::SM, 175, "San Marino", "EUR", "en-GB", "en-sm", "378"),
+    entry(Country::RS, 301, "Serbia", "EUR", "sr-Latn-BA", "en-rs", "381"),
+    entry(Country::SK, 182, "Slovakia", "EUR", "sk-SK", "en-sk", "421"),
+    entry(Country::SI, 183, "Slovenia", "EUR", "sl-SI", "en-si", "386"),
+    entry(Country::ES, 187, "Spain", "EUR", "es-ES", "en-es", "34"),
+    entry(Country::IC, 305, "Spain - Canary Islands", "EUR", "es-ES", "en-ic", "34"),
+    entry(Country::SE, 196, "Sweden", "SEK", "sv-SE", "en-se", "46"),
+    entry(Country::CH, 197, "Switzerland", "CHF", "de-CH", "en-ch", "41"),
+    entry(Country::TR, 207, "Turkey", "EUR", "tr-TR", "en-tr", "90"),
+    entry(Country::UA, 214, "Ukraine", "EUR", "uk-UA", "en-ua", "380"),
+    entry(Country::GB, 215, "United Kingdom", "GBP", "en-GB", "en-gb", "44"),
+    entry(Country::VA, 220, "Vatican City State", "EUR", "en-GB", "en-va", "39"),
+    entry(Country::CA, 36, "Canada", "CAD", "en-CA", "en-ca", "1"),
+    entry(Country::PR, 166, "Puerto Rico", "USD", "es-PR", "en-pr", "1"),
+    entry(Country::US, 216, "United States", "USD", "en-US", "en-us", "1"),
+];
+
+const fn entry(
+    country: Country,
+    id: isize,
+    name: &'static str,
+    currency: &'static str,
+    language: &'static str,
+    locale: &'static str,
+    calling_code: &'static str,
+) -> CountryData {
+    CountryData {
+        country,
+        fps: FPSCountry { id, name },
+        currency,
+        language,
+        locale,
+        calling_code,
+    }
+}
+
+impl<'de> Deserialize<'de> for Country {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Country, D::Error> {
+        let code = String::deserialize(deserializer)?;
+
+        Country::from_str(&code)
+            .map_err(|_| de::Error::custom(format!("unknown country code `{}`", code)))
+    }
+}
+
 impl Country {
-    pub fn fps_base_url(&self) -> &'static str {
-        {
-            match self {
-                Country::AL => "https://www.blackandwhite-ff.com/en-al",
-                Country::AD => "https://www.blackandwhite-ff.com/en-ad",
-                Country::AT => "https://www.blackandwhite-ff.com/en-at",
-                Country::AZ => "https://www.blackandwhite-ff.com/en-az",
-                Country::BY => "https://www.blackandwhite-ff.com/en-by",
-                Country::BE => "https://www.blackandwhite-ff.com/en-be",
-                Country::BA => "https://www.blackandwhite-ff.com/en-ba",
-                Country::BG => "https://www.blackandwhite-ff.com/en-bg",
-                Country::HR => "https://www.blackandwhite-ff.com/en-hr",
-                Country::CY => "https://www.blackandwhite-ff.com/en-cy",
-                Country::CZ => "https://www.blackandwhite-ff.com/en-cz",
-                Country::DK => "https://www.blackandwhite-ff.com/en-dk",
-                Country::EE => "https://www.blackandwhite-ff.com/en-ee",
-                Country::FO => "https://www.blackandwhite-ff.com/en-fo",
-                Country::FI => "https://www.blackandwhite-ff.com/en-fi",
-                Country::FR => "https://www.blackandwhite-ff.com/en-fr",
-                Country::DE => "https://www.blackandwhite-ff.com/en-de",
-                Country::GI => "https://www.blackandwhite-ff.com/en-gi",
-                Country::GR => "https://www.blackandwhite-ff.com/en-gr",
-                Country::GL => "https://www.blackandwhite-ff.com/en-gl",
-                Country::GG => "https://www.blackandwhite-ff.com/en-gg",
-                Country::HU => "https://www.blackandwhite-ff.com/en-hu",
-                Country::IS => "https://www.blackandwhite-ff.com/en-is",
-                Country::IE => "https://www.blackandwhite-ff.com/en-ie",
-                Country::IT => "https://www.blackandwhite-ff.com/it-it",
-                Country::JE => "https://www.blackandwhite-ff.com/en-je",
-                Country::KV => "https://www.blackandwhite-ff.com/en-kv",
-                Country::LV => "https://www.blackandwhite-ff.com/en-lv",
-                Country::LI => "https://www.blackandwhite-ff.com/en-li",
-                Country::LT => "https://www.blackandwhite-ff.com/en-lt",
-                Country::LU => "https://www.blackandwhite-ff.com/en-lu",
-                Country::MT => "https://www.blackandwhite-ff.com/en-mt",
-                Country::MD => "https://www.blackandwhite-ff.com/en-md",
-                Country::MC => "https://www.blackandwhite-ff.com/en-mc",
-                Country::ME => "https://www.blackandwhite-ff.com/en-me",
-                Country::NL => "https://www.blackandwhite-ff.com/en-nl",
-                Country::MK => "https://www.blackandwhite-ff.com/en-mk",
-                Country::NO => "https://www.blackandwhite-ff.com/en-no",
-                Country::PL => "https://www.blackandwhite-ff.com/en-pl",
-                Country::PT => "https://www.blackandwhite-ff.com/en-pt",
-                Country::RO => "https://www.blackandwhite-ff.com/en-ro",
-                Country::RU => "https://www.blackandwhite-ff.com/en-ru",
-                Country::SM => "https://www.blackandwhite-ff.com/en-sm",
-                Country::RS => "https://www.blackandwhite-ff.com/en-rs",
-                Country::SK => "https://www.blackandwhite-ff.com/en-sk",
-                Country::SI => "https://www.blackandwhite-ff.com/en-si",
-                Country::ES => "https://www.blackandwhite-ff.com/en-es",
-                Country::IC => "https://www.blackandwhite-ff.com/en-ic",
-                Country::SE => "https://www.blackandwhite-ff.com/en-se",
-                Country::CH => "https://www.blackandwhite-ff.com/en-ch",
-                Country::TR => "https://www.blackandwhite-ff.com/en-tr",
-                Country::UA => "https://www.blackandwhite-ff.com/en-ua",
-                Country::GB => "https://www.blackandwhite-ff.com/en-gb",
-                Country::VA => "https://www.blackandwhite-ff.com/en-va",
-                Country::CA => "https://www.blackandwhite-ff.com/en-ca",
-                Country::PR => "https://www.blackandwhite-ff.com/en-pr",
-                Country::US => "https://www.blackandwhite-ff.com/en-us",
-            }
-        }
+    pub fn data(&self) -> &'static CountryData {
+        COUNTRIES
+            .iter()
+            .find(|data| data.country == *self)
+            .unwrap_or_else(|| panic!("{:?} is missing from COUNTRIES", self))
+    }
+
+    pub fn fps_locale(&self) -> &'static str {
+        self.data().locale
     }
 
     pub fn fps_country(&self) -> &'static FPSCountry {
-        {
-            match self {
-                Country::AL => &FPSCountry {
-                    id: 2,
-                    name: "Albania",
-                },
-                Country::AD => &FPSCountry {
-                    id: 307,
-                    name: "Andorra",
-                },
-                Country::AT => &FPSCountry {
-                    id: 13,
-                    name: "Austria",
-                },
-                Country::AZ => &FPSCountry {
-                    id: 14,
-                    name: "Azerbaijan",
-                },
-                Country::BY => &FPSCountry {
-                    id: 19,
-                    name: "Belarus",
-                },
-                Country::BE => &FPSCountry {
-                    id: 20,
-                    name: "Belgium",
-                },
-                Country::BA => &FPSCountry {
-                    id: 304,
-                    name: "Bosnia and Herzegovina",
-                },
-                Country::BG => &FPSCountry {
-                    id: 31,
-                    name: "Bulgaria",
-                },
-                Country::HR => &FPSCountry {
-                    id: 51,
-                    name: "Croatia",
-                },
-                Country::CY => &FPSCountry {
-                    id: 52,
-                    name: "Cyprus",
-                },
-                Country::CZ => &FPSCountry {
-                    id: 53,
-                    name: "Czech Republic",
-                },
-                Country::DK => &FPSCountry {
-                    id: 54,
-                    name: "Denmark",
-                },
-                Country::EE => &FPSCountry {
-                    id: 63,
-                    name: "Estonia",
-                },
-                Country::FO => &FPSCountry {
-                    id: 65,
-                    name: "Faeroe Islands",
-                },
-                Country::FI => &FPSCountry {
-                    id: 69,
-                    name: "Finland",
-                },
-                Country::FR => &FPSCountry {
-                    id: 70,
-                    name: "France",
-                },
-                Country::DE => &FPSCountry {
-                    id: 77,
-                    name: "Germany",
-                },
-                Country::GI => &FPSCountry {
-                    id: 79,
-                    name: "Gibraltar",
-                },
-                Country::GR => &FPSCountry {
-                    id: 80,
-                    name: "Greece",
-                },
-                Country::GL => &FPSCountry {
-                    id: 81,
-                    name: "Greenland",
-                },
-                Country::GG => &FPSCountry {
-                    id: 86,
-                    name: "Guernsey, C.I.",
-                },
-                Country::HU => &FPSCountry {
-                    id: 94,
-                    name: "Hungary",
-                },
-                Country::IS => &FPSCountry {
-                    id: 95,
-                    name: "Iceland",
-                },
-                Country::IE => &FPSCountry {
-                    id: 98,
-                    name: "Ireland",
-                },
-                Country::IT => &FPSCountry {
-                    id: 101,
-                    name: "Italy",
-                },
-                Country::JE => &FPSCountry {
-                    id: 104,
-                    name: "Jersey, C.I.",
-                },
-                Country::KV => &FPSCountry {
-                    id: 311,
-                    name: "Kosovo",
-                },
-                Country::LV => &FPSCountry {
-                    id: 113,
-                    name: "Latvia",
-                },
-                Country::LI => &FPSCountry {
-                    id: 117,
-                    name: "Liechtenstein",
-                },
-                Country::LT => &FPSCountry {
-                    id: 118,
-                    name: "Lithuania",
-                },
-                Country::LU => &FPSCountry {
-                    id: 119,
-                    name: "Luxembourg",
-                },
-                Country::MT => &FPSCountry {
-                    id: 126,
-                    name: "Malta",
-                },
-                Country::MD => &FPSCountry {
-                    id: 133,
-                    name: "Moldova, Republic of",
-                },
-                Country::MC => &FPSCountry {
-                    id: 134,
-                    name: "Monaco",
-                },
-                Country::ME => &FPSCountry {
-                    id: 302,
-                    name: "Montenegro",
-                },
-                Country::NL => &FPSCountry {
-                    id: 144,
-                    name: "Netherlands",
-                },
-                Country::MK => &FPSCountry {
-                    id: 303,
-                    name: "North Macedonia",
-                },
-                Country::NO => &FPSCountry {
-                    id: 153,
-                    name: "Norway",
-                },
-                Country::PL => &FPSCountry {
-                    id: 164,
-                    name: "Poland",
-                },
-                Country::PT => &FPSCountry {
-                    id: 165,
-                    name: "Portugal",
-                },
-                Country::RO => &FPSCountry {
-                    id: 169,
-                    name: "Romania",
-                },
-                Country::RU => &FPSCountry {
-                    id: 170,
-                    name: "Russian Federation",
-                },
-                Country::SM => &FPSCountry {
-                    id: 175,
-                    name: "San Marino",
-                },
-                Country::RS => &FPSCountry {
-                    id: 301,
-                    name: "Serbia",
-                },
-                Country::SK => &FPSCountry {
-                    id: 182,
-                    name: "Slovakia",
-                },
-                Country::SI => &FPSCountry {
-                    id: 183,
-                    name: "Slovenia",
-                },
-                Country::ES => &FPSCountry {
-                    id: 187,
-                    name: "Spain",
-                },
-                Country::IC => &FPSCountry {
-                    id: 305,
-                    name: "Spain - Canary Islands",
-                },
-                Country::SE => &FPSCountry {
-                    id: 196,
-                    name: "Sweden",
-                },
-                Country::CH => &FPSCountry {
-                    id: 197,
-                    name: "Switzerland",
-                },
-                Country::TR => &FPSCountry {
-                    id: 207,
-                    name: "Turkey",
-                },
-                Country::UA => &FPSCountry {
-                    id: 214,
-                    name: "Ukraine",
-                },
-                Country::GB => &FPSCountry {
-                    id: 215,
-                    name: "United Kingdom",
-                },
-                Country::VA => &FPSCountry {
-                    id: 220,
-                    name: "Vatican City State",
-                },
-                Country::CA => &FPSCountry {
-                    id: 36,
-                    name: "Canada",
-                },
-                Country::PR => &FPSCountry {
-                    id: 166,
-                    name: "Puerto Rico",
-                },
-                Country::US => &FPSCountry {
-                    id: 216,
-                    name: "United States",
-                },
-            }
-        }
+        &self.data().fps
     }
 
     pub fn fps_currency(&self) -> &'static str {
-        {
-            match self {
-                Country::AL => "USD",
-                Country::AD => "EUR",
-                Country::AT => "EUR",
-                Country::AZ => "USD",
-                Country::BY => "USD",
-                Country::BE => "EUR",
-                Country::BA => "EUR",
-                Country::BG => "EUR",
-                Country::HR => "EUR",
-                Country::CY => "EUR",
-                Country::CZ => "EUR",
-                Country::DK => "DKK",
-                Country::EE => "EUR",
-                Country::FO => "USD",
-                Country::FI => "EUR",
-                Country::FR => "EUR",
-                Country::DE => "EUR",
-                Country::GI => "GBP",
-                Country::GR => "EUR",
-                Country::GL => "USD",
-                Country::GG => "GBP",
-                Country::HU => "EUR",
-                Country::IS => "USD",
-                Country::IE => "EUR",
-                Country::IT => "EUR",
-                Country::JE => "GBP",
-                Country::KV => "USD",
-                Country::LV => "EUR",
-                Country::LI => "CHF",
-                Country::LT => "EUR",
-                Country::LU => "EUR",
-                Country::MT => "EUR",
-                Country::MD => "USD",
-                Country::MC => "EUR",
-                Country::ME => "EUR",
-                Country::NL => "EUR",
-                Country::MK => "EUR",
-                Country::NO => "USD",
-                Country::PL => "PLN",
-                Country::PT => "EUR",
-                Country::RO => "EUR",
-                Country::RU => "RUB",
-                Country::SM => "EUR",
-                Country::RS => "EUR",
-                Country::SK => "EUR",
-                Country::SI => "EUR",
-                Country::ES => "EUR",
-                Country::IC => "EUR",
-                Country::SE => "SEK",
-                Country::CH => "CHF",
-                Country::TR => "EUR",
-                Country::UA => "EUR",
-                Country::GB => "GBP",
-                Country::VA => "EUR",
-                Country::CA => "CAD",
-                Country::PR => "USD",
-                Country::US => "USD",
-            }
-        }
+        self.data().currency
     }
 
     pub fn accept_language(&self) -> &'static str {
-        {
-            match self {
-                Country::AL => "sq-AL",
-                Country::AD => "en-GB",
-                Country::AT => "de-AT",
-                Country::AZ => "az-Latn-AZ",
-                Country::BY => "be-BY",
-                Country::BE => "fr-BE",
-                Country::BA => "en-GB",
-                Country::BG => "bg-BG",
-                Country::HR => "hr-HR",
-                Country::CY => "en-GB",
-                Country::CZ => "cs-CZ",
-                Country::DK => "da-DK",
-                Country::EE => "et-EE",
-                Country::FO => "en-GB",
-                Country::FI => "fi-FI",
-                Country::FR => "fr-FR",
-                Country::DE => "de-DE",
-                Country::GI => "en-GB",
-                Country::GR => "el-GR",
-                Country::GL => "kl-GL",
-                Country::GG => "en-GB",
-                Country::HU => "hu-HU",
-                Country::IS => "is-IS",
-                Country::IE => "en-IE",
-                Country::IT => "it-IT",
-                Country::JE => "en-GB",
-                Country::KV => "en-GB",
-                Country::LV => "lv-LV",
-                Country::LI => "de-LI",
-                Country::LT => "lt-LT",
-                Country::LU => "fr-LU",
-                Country::MT => "mt-MT",
-                Country::MD => "en-GB",
-                Country::MC => "fr-MC",
-                Country::ME => "en-GB",
-                Country::NL => "nl-NL",
-                Country::MK => "en-GB",
-                Country::NO => "nb-NO",
-                Country::PL => "en-US",
-                Country::PT => "pt-PT",
-                Country::RO => "ro-RO",
-                Country::RU => "ru-RU",
-                Country::SM => "en-GB",
-                Country::RS => "sr-Latn-BA",
-                Country::SK => "sk-SK",
-                Country::SI => "sl-SI",
-                Country::ES => "es-ES",
-                Country::IC => "es-ES",
-                Country::SE => "sv-SE",
-                Country::CH => "de-CH",
-                Country::TR => "tr-TR",
-                Country::UA => "uk-UA",
-                Country::GB => "en-GB",
-                Country::VA => "en-GB",
-                Country::CA => "en-CA",
-                Country::PR => "es-PR",
-                Country::US => "en-US",
-            }
+        self.data().language
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use strum::{AsStaticRef, IntoEnumIterator};
+    use url::Url;
+
+    #[test]
+    fn every_country_has_one_entry() {
+        for country in Country::iter() {
+            let entries = COUNTRIES
+                .iter()
+                .filter(|data| data.country == country)
+                .count();
+            assert_eq!(entries, 1, "{:?}", country);
+        }
+
+        let ids = COUNTRIES
+            .iter()
+            .map(|data| data.fps.id)
+            .collect::<HashSet<_>>();
+        assert_eq!(ids.len(), COUNTRIES.len());
+    }
+
+    #[test]
+    fn locales_are_url_path_segments() {
+        for country in Country::iter() {
+            let locale = country.fps_locale();
+            let url = Url::parse(&format!("https://www.emiliopucci.com/{}/", locale)).unwrap();
+
+            assert_eq!(url.path(), format!("/{}/", locale), "{:?}", country);
+            assert_eq!(locale, locale.to_lowercase(), "{:?}", country);
         }
     }
+
+    #[test]
+    fn currencies_are_iso_codes() {
+        for country in Country::iter() {
+            let currency = country.fps_currency();
+
+            assert_eq!(currency.len(), 3, "{:?}", country);
+            assert!(
+                currency.chars().all(|c| c.is_ascii_uppercase()),
+                "{:?}",
+                country
+            );
+        }
+    }
+
+    #[test]
+    fn languages_are_well_formed_tags() {
+        for country in Country::iter() {
+            let tag = country.accept_language();
+            let subtags = tag.split('-').collect::<Vec<_>>();
+
+            let (language, script, region) = match subtags.as_slice() {
+                [language, region] => (*language, None, *region),
+                [language, script, region] => (*language, Some(*script), *region),
+                _ => panic!("{:?}: {}", country, tag),
+            };
+
+            assert!(
+                (2..=3).contains(&language.len())
+                    && language.chars().all(|c| c.is_ascii_lowercase()),
+                "{:?}: {}",
+                country,
+                tag
+            );
+            assert!(
+                script.is_none_or(|script| {
+                    script.len() == 4
+                        && script.starts_with(|c: char| c.is_ascii_uppercase())
+                        && script[1..].chars().all(|c| c.is_ascii_lowercase())
+                }),
+                "{:?}: {}",
+                country,
+                tag
+            );
+            assert!(
+                region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()),
+                "{:?}: {}",
+                country,
+                tag
+            );
+        }
+    }
+
+    #[test]
+    fn calling_codes_are_digits() {
+        for country in Country::iter() {
+            let code = country.data().calling_code;
+
+            assert!((1..=3).contains(&code.len()), "{:?}", country);
+            assert!(
+                code.chars().all(|c| c.is_ascii_digit()) && !code.starts_with('0'),
+                "{:?}",
+                country
+            );
+        }
+    }
+
+    #[test]
+    fn deserializes_any_case() {
+        let parse = |json: &str| serde_json::from_str::<Country>(json);
+
+        assert_eq!(parse(r#""GB""#).unwrap(), Country::GB);
+        assert_eq!(parse(r#""de""#).unwrap(), Country::DE);
+        assert_eq!(parse(r#""Us""#).unwrap(), Country::US);
+        assert!(parse(r#""ZZ""#)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown country code `ZZ`"));
+    }
+
+    #[test]
+    fn table_matches_storefront_codes() {
+        assert_eq!(Country::GB.fps_locale(), "en-gb");
+        assert_eq!(Country::IT.fps_locale(), "it-it");
+        assert_eq!(Country::GB.fps_country().id, 215);
+        assert_eq!(Country::US.as_static(), "US");
+    }
 }
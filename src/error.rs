@@ -8,12 +8,27 @@ pub enum Error {
     #[error("from_utf8={0}")]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
 
-    #[error("invald_header_value={0}")]
-    InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderName),
+    #[error("invalid_config={0}")]
+    InvalidConfig(String),
+
+    #[error("invalid_header_value={0}")]
+    InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
 
     #[error("tokio_ join={0}")]
     JoinError(#[from] tokio::task::JoinError),
 
+    #[error("order_totals={0}")]
+    OrderTotals(String),
+
+    #[error("payment_form={0}")]
+    PaymentForm(String),
+
+    #[error("payment_method={0}")]
+    PaymentMethod(String),
+
+    #[error("payment_pending order={0}")]
+    PaymentPending(i64),
+
     #[error("url_parse={0}")]
     ParseError(#[from] url::ParseError),
 
@@ -26,15 +41,41 @@ pub enum Error {
     #[error("serde_json={0}")]
     SerdeJSON(#[from] serde_json::Error),
 
+    #[error("serde_yaml={0}")]
+    SerdeYAML(#[from] serde_yaml::Error),
+
+    #[error("shipping={0}")]
+    Shipping(String),
+
     #[error("system_time={0}")]
     SystemTimeError(#[from] SystemTimeError),
 
     #[error("timeout={0}")]
     TimeoutError(#[from] tokio::time::error::Elapsed),
 
+    #[error("toml={0}")]
+    Toml(#[from] toml::de::Error),
+
     #[error("tokio_io={0}")]
     TokioIO(#[from] tokio::io::Error),
 
+    #[error("vault={0}")]
+    Vault(String),
+
     #[error("unknown={0}")]
     Unknown(String),
 }
+
+impl Error {
+    /// Whether retrying the same request might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Reqwest(why) => match why.status() {
+                Some(status) => status.is_server_error() || status.as_u16() == 429,
+                None => why.is_timeout() || why.is_connect() || why.is_request(),
+            },
+            Error::TimeoutError(_) | Error::PaymentPending(_) => true,
+            _ => false,
+        }
+    }
+}
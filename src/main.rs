@@ -1,31 +1,84 @@
+mod address;
+mod card;
+mod checkout;
+mod client;
+mod config;
 mod country;
 mod error;
+#[cfg(test)]
+mod mock;
 mod model;
 mod monitor;
+mod payment;
+mod phone;
+mod price;
+mod region;
+mod scale;
+mod secret;
+mod shipping;
+mod size;
+mod storefront;
 mod task;
+mod vault;
 
+use checkout::PurchaseCounter;
 use country::Country;
 pub use error::Error;
 use futures::{stream::futures_unordered::FuturesUnordered, StreamExt};
-use log::error;
-use model::{Config, Variant};
-use monitor::Monitor;
-use std::{collections::HashMap, fs::File};
+use log::{error, info};
+use monitor::{Monitor, Release};
+use std::{collections::HashMap, path::Path, time::Duration};
 use tokio::sync::broadcast::{self, Sender};
 
-use crate::task::Task;
+use crate::task::{Task, TaskOptions};
+use crate::vault::Vault;
+
+const USAGE: &str =
+    "usage: emilio-pucci [--config <path>] [vault import <vault> <profiles.json> | vault list <vault>]";
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     pretty_env_logger::init_timed();
 
-    let file = File::open("config.json")?;
-    let config: Config = serde_json::from_reader(file)?;
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+    let config_flag = match args.iter().position(|arg| arg == "--config" || arg == "-c") {
+        Some(index) if index + 1 < args.len() => {
+            let path = args.remove(index + 1);
+            args.remove(index);
+            Some(path)
+        }
+        Some(_) => return Err(Error::Unknown(USAGE.into())),
+        None => None,
+    };
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["vault", "import", vault, profiles] => {
+            return vault::import(Path::new(vault), Path::new(profiles))
+        }
+        ["vault", "list", vault] => return vault::list(Path::new(vault)),
+        [] => {}
+        _ => return Err(Error::Unknown(USAGE.into())),
+    }
+
+    let mut config = config::load(&config::path(config_flag))?;
+    let vault = match &config.vault {
+        Some(path) => Some(Vault::open(path, &vault::passphrase()?)?),
+        None => None,
+    };
+    config.validate(vault.as_ref())?;
 
     let mut tasks = FuturesUnordered::new();
+    let mut monitor_handles = Vec::new();
+    let mut states = Vec::new();
 
-    for task_config in config.tasks {
-        let mut monitors: HashMap<Country, Sender<(i64, Vec<Variant>)>> = HashMap::new();
+    for task_config in &config.tasks {
+        let storefront = config.storefront(&task_config.storefront)?;
+        let mut monitors: HashMap<Country, Sender<Release>> = HashMap::new();
 
         let countries = task_config
             .profiles
@@ -34,29 +87,74 @@ async fn main() -> Result<(), Error> {
             .collect::<Vec<_>>();
 
         for country in countries {
-            let (sender, _) = broadcast::channel::<(i64, Vec<Variant>)>(32);
-            let mut monitor = Monitor::new(task_config.product.clone(), &country, sender.clone())?;
+            let (sender, _) = broadcast::channel::<Release>(32);
+            let mut monitor = Monitor::new(
+                task_config.product.clone(),
+                &storefront,
+                &country,
+                Duration::from_millis(task_config.poll_interval_ms),
+                task_config.price.clone(),
+                sender.clone(),
+            )?;
             monitors.insert(country, sender);
 
             let handle = tokio::task::spawn(async move { monitor.start().await });
 
-            tasks.push(handle);
+            monitor_handles.push(handle);
         }
 
-        for profile in task_config.profiles {
+        let purchases = PurchaseCounter::new(task_config.limits.total);
+
+        for profile in &task_config.profiles {
             let sender = monitors.get(&profile.delivery.country).unwrap();
-            let mut task = Task::new(profile, sender.subscribe())?;
-            let handle = tokio::task::spawn(async move { task.start().await });
+            let mut task = Task::new(
+                profile.clone(),
+                storefront.clone(),
+                TaskOptions {
+                    policy: task_config.retry,
+                    dry_run: task_config.dry_run.unwrap_or(config.dry_run),
+                    max_purchases: task_config.limits.per_profile,
+                    max_declines: task_config.limits.declines,
+                    purchases: purchases.clone(),
+                    size_strategy: task_config.size_strategy,
+                    seed: task_config.seed,
+                    price: task_config.price.clone(),
+                    scales: config.scales.clone(),
+                },
+                sender.subscribe(),
+            )?;
+            states.push((profile.alias.clone(), task.state()));
+            let alias = profile.alias.clone();
+            let handle = tokio::task::spawn(async move { (alias, task.run().await) });
 
             tasks.push(handle);
         }
     }
 
     while let Some(join) = tasks.next().await {
-        if let Err(why) = join.unwrap() {
-            error!("message={},  error=\"task failure\"", why)
+        match join.unwrap() {
+            (alias, Ok(outcomes)) => {
+                for outcome in outcomes {
+                    info!("profile={} outcome={}", alias, outcome);
+                }
+            }
+            (alias, Err(why)) => {
+                error!(
+                    "profile={} error=\"{}\" message=\"task failure\"",
+                    alias, why
+                )
+            }
         }
     }
 
+    // Monitors poll forever, stop them once no task is left to buy.
+    for handle in monitor_handles {
+        handle.abort();
+    }
+
+    for (alias, state) in states {
+        info!("profile={} state={}", alias, *state.borrow());
+    }
+
     Ok(())
 }
@@ -0,0 +1,515 @@
+//! In-process stand-in for a storefront API, used by the end-to-end tests.
+//!
+//! Failures are scripted with scenario files under `tests/scenarios`, see `Scenario`.
+
+use crate::country::Country;
+use crate::model::Profile;
+use crate::storefront::Storefront;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use strum::AsStaticRef;
+use tokio::sync::oneshot;
+use url::Url;
+
+const PRODUCT: &str = include_str!("../tests/fixtures/product.json");
+const ORDER: &str = include_str!("../tests/fixtures/order.json");
+const FINALIZE: &str = include_str!("../tests/fixtures/finalize.json");
+const CSRF: &str = "csrf-token";
+
+pub const PRODUCT_ID: &str = "16472289";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    pub method: Method,
+    /// Path below `/api/`, e.g. `checkout/v1/orders/1/finalize`, or `gateway/...` for the
+    /// payment gateway.
+    pub path: String,
+    pub body: Value,
+}
+
+/// Scripted behaviour per endpoint, loaded from `tests/scenarios/<name>.json`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Scenario {
+    pub product: ProductScript,
+    pub me: Script,
+    pub create_order: Script,
+    pub patch_address: Script,
+    pub finalize: FinalizeScript,
+    /// Quote orders in this currency instead of the one the client asks for.
+    pub currency: Option<String>,
+}
+
+/// The n-th call to an endpoint fails with `failures[n]` and is held back for `delays_ms[n]`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Script {
+    pub failures: Vec<u16>,
+    pub delays_ms: Vec<u64>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProductScript {
+    #[serde(flatten)]
+    pub script: Script,
+    /// Number of polls answered with every variant sold out.
+    pub out_of_stock_polls: usize,
+    /// Serve `tests/fixtures/<fixture>.json` instead of `product.json`.
+    pub fixture: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FinalizeScript {
+    #[serde(flatten)]
+    pub script: Script,
+    /// Decline every payment with this reason.
+    pub decline: Option<String>,
+    /// Ask for 3-D Secure at this URL instead of authorising.
+    pub redirect_url: Option<String>,
+    /// Answer with an empty success body, as a proxy in front of the storefront may.
+    pub empty: bool,
+    /// Leave the payment pending instead of authorising.
+    pub pending: bool,
+    /// Order polls answered with the order still open after a pending payment.
+    pub pending_polls: usize,
+    /// Cancel the order of a pending payment once those polls are over, instead of placing it.
+    pub cancel_pending: bool,
+}
+
+impl Scenario {
+    pub fn load(name: &str) -> Scenario {
+        let path = format!(
+            "{}/tests/scenarios/{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        );
+        let file = std::fs::File::open(&path).unwrap_or_else(|why| panic!("{}: {}", path, why));
+
+        serde_json::from_reader(file).unwrap()
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    scenario: Scenario,
+    calls: HashMap<&'static str, usize>,
+    orders: i64,
+    /// Orders created with `usePaymentIntent`, which get a payment intent id.
+    intents: HashSet<i64>,
+    /// Shipping options set on each order, one per group of merchants.
+    shipping: HashMap<i64, Vec<Value>>,
+    /// Currency each order is quoted in, from the `FF-Currency` header.
+    currencies: HashMap<i64, String>,
+    /// Orders whose payment was left pending, with the number of times each was polled.
+    pending: HashMap<i64, usize>,
+    requests: Vec<MockRequest>,
+}
+
+impl State {
+    /// Counts a call to `route` and returns its scripted failure status and delay.
+    fn script(&mut self, route: &'static str, script: &Script) -> (Option<StatusCode>, u64) {
+        let calls = self.calls.entry(route).or_insert(0);
+        let call = *calls;
+        *calls += 1;
+
+        let failure = script
+            .failures
+            .get(call)
+            .map(|status| StatusCode::from_u16(*status).unwrap());
+        let delay = script.delays_ms.get(call).copied().unwrap_or(0);
+
+        (failure, delay)
+    }
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> MockServer {
+        MockServer::with_scenario(Scenario::default()).await
+    }
+
+    pub async fn with_scenario(scenario: Scenario) -> MockServer {
+        let state = Arc::new(Mutex::new(State {
+            scenario,
+            ..State::default()
+        }));
+        let service_state = state.clone();
+
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move { Ok::<_, Infallible>(service_fn(move |request| handle(state.clone(), request))) }
+        });
+
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, signal) = oneshot::channel::<()>();
+
+        tokio::spawn(server.with_graceful_shutdown(async {
+            signal.await.ok();
+        }));
+
+        MockServer {
+            addr,
+            state,
+            _shutdown: shutdown,
+        }
+    }
+
+    pub fn storefront(&self) -> Storefront {
+        Storefront {
+            name: "Mock".into(),
+            base_url: Url::parse(&format!("http://{}", self.addr)).unwrap(),
+            locale_pattern: "/{locale}".into(),
+            static_name: "mock".into(),
+            folder_name: "mock-21".into(),
+            timeout_ms: 250,
+            payment_gateway: None,
+        }
+    }
+
+    /// The mock storefront, paying through the mock payment gateway.
+    pub fn gateway_storefront(&self) -> Storefront {
+        Storefront {
+            payment_gateway: Some(Url::parse(&format!("http://{}/gateway", self.addr)).unwrap()),
+            ..self.storefront()
+        }
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    pub fn count(&self, method: Method, path: &str) -> usize {
+        self.requests()
+            .iter()
+            .filter(|request| request.method == method && request.path == path)
+            .count()
+    }
+
+    /// Polls the request log until `path` has been requested, or gives up after five seconds.
+    pub async fn wait_for(&self, method: Method, path: &str) -> Option<MockRequest> {
+        for _ in 0..500 {
+            let found = self
+                .requests()
+                .into_iter()
+                .find(|request| request.method == method && request.path == path);
+
+            if found.is_some() {
+                return found;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        None
+    }
+}
+
+pub fn profile(country: Country) -> Profile {
+    let address = json!({
+        "firstName": "Jane",
+        "lastName": "Doe",
+        "address1": "1 Test Street",
+        "zip": "SW1A 1AA",
+        "city": "London",
+        "country": country.as_static(),
+    });
+
+    serde_json::from_value(json!({
+        "email": "jane@example.com",
+        "phone": "+447700900000",
+        "card": {
+            "number": "4111111111111111",
+            "expiryMonth": 12,
+            "expiryYear": 2030,
+            "cvv": "123",
+        },
+        "delivery": address,
+        "billing": address,
+    }))
+    .unwrap()
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let csrf = request
+        .headers()
+        .get("X-CSRF-TOKEN")
+        .and_then(|token| token.to_str().ok())
+        .map(String::from);
+    let currency = request
+        .headers()
+        .get("FF-Currency")
+        .and_then(|currency| currency.to_str().ok())
+        .map(String::from);
+    let path = match uri.path().split_once("/api/") {
+        Some((_, path)) => path.trim_end_matches('/').to_string(),
+        None if uri.path().starts_with("/gateway") => uri.path().trim_matches('/').to_string(),
+        None => return Ok(respond(StatusCode::NOT_FOUND, Value::Null)),
+    };
+
+    let bytes = hyper::body::to_bytes(request.into_body())
+        .await
+        .unwrap_or_default();
+    let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+    let (status, body, delay) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(MockRequest {
+            method: method.clone(),
+            path: path.clone(),
+            body: body.clone(),
+        });
+
+        let segments = path.split('/').collect::<Vec<_>>();
+        if let ["gateway", ..] = segments.as_slice() {
+            let query = uri.query().unwrap_or_default();
+            return Ok(gateway(&segments[1..], method, query, csrf.as_deref()));
+        }
+
+        route(&mut state, method, &segments, &body, currency.as_deref())
+    };
+
+    tokio::time::sleep(Duration::from_millis(delay)).await;
+
+    Ok(respond(status, body))
+}
+
+fn route(
+    state: &mut State,
+    method: Method,
+    segments: &[&str],
+    body: &Value,
+    currency: Option<&str>,
+) -> (StatusCode, Value, u64) {
+    let scenario = state.scenario.clone();
+
+    let (route, script) = match (&method, segments) {
+        (&Method::GET, ["products", _]) => ("product", &scenario.product.script),
+        (&Method::GET, ["users", "me"]) => ("me", &scenario.me),
+        (&Method::POST, ["checkout", "v1", "orders"]) => ("create_order", &scenario.create_order),
+        (&Method::PATCH, ["checkout", "v1", "orders", _]) => {
+            ("patch_address", &scenario.patch_address)
+        }
+        (&Method::POST, ["checkout", "v1", "orders", _, "finalize"]) => {
+            ("finalize", &scenario.finalize.script)
+        }
+        _ => ("other", &Script::default()),
+    };
+
+    let call = state.calls.get(route).copied().unwrap_or(0);
+    let (failure, delay) = state.script(route, script);
+    if let Some(status) = failure {
+        return (status, Value::Null, delay);
+    }
+
+    let (status, body) = match (method, segments) {
+        (Method::GET, ["products", PRODUCT_ID]) => {
+            let mut product: Value = match &scenario.product.fixture {
+                Some(fixture) => serde_json::from_str(&fixture_file(fixture)).unwrap(),
+                None => serde_json::from_str(PRODUCT).unwrap(),
+            };
+            if call < scenario.product.out_of_stock_polls {
+                for variant in product["result"]["variants"].as_array_mut().unwrap() {
+                    variant["quantity"] = json!(0);
+                }
+            }
+
+            (StatusCode::OK, product)
+        }
+        (Method::GET, ["users", "me"]) => (StatusCode::OK, json!({ "id": 1 })),
+        (Method::POST, ["checkout", "v1", "orders"]) => {
+            state.orders += 1;
+            if body["usePaymentIntent"] == json!(true) {
+                state.intents.insert(state.orders);
+            }
+            if let Some(currency) = scenario.currency.as_deref().or(currency) {
+                state.currencies.insert(state.orders, currency.into());
+            }
+
+            (StatusCode::OK, order(state, state.orders))
+        }
+        (Method::GET, ["checkout", "v1", "orders", id]) => match id.parse() {
+            Ok(id) if id <= state.orders => {
+                let mut order = order(state, id);
+                if let Some(polls) = state.pending.get_mut(&id) {
+                    *polls += 1;
+                    order["checkoutOrder"]["status"] = json!(match &scenario.finalize {
+                        finalize if *polls <= finalize.pending_polls => 0,
+                        finalize if finalize.cancel_pending => 2,
+                        _ => 1,
+                    });
+                }
+
+                (StatusCode::OK, order)
+            }
+            _ => (StatusCode::NOT_FOUND, Value::Null),
+        },
+        (Method::PATCH, ["checkout", "v1", "orders", id]) => match id.parse() {
+            Ok(id) if id <= state.orders => (StatusCode::OK, order(state, id)),
+            _ => (StatusCode::NOT_FOUND, Value::Null),
+        },
+        (Method::PUT, ["checkout", "v1", "orders", id, "shippingOptions"]) => {
+            match (id.parse(), body.as_array()) {
+                (Ok(id), Some(options)) if id <= state.orders && !options.is_empty() => {
+                    state.shipping.insert(id, options.clone());
+                    (StatusCode::OK, order(state, id))
+                }
+                (Ok(id), _) if id <= state.orders => (StatusCode::BAD_REQUEST, Value::Null),
+                _ => (StatusCode::NOT_FOUND, Value::Null),
+            }
+        }
+        (Method::POST, ["checkout", "v1", "orders", id, "finalize"]) => {
+            match (id.parse(), &scenario.finalize) {
+                (Ok(id), _) if id > state.orders => (StatusCode::NOT_FOUND, Value::Null),
+                (
+                    Ok(_),
+                    FinalizeScript {
+                        decline: Some(reason),
+                        ..
+                    },
+                ) => (
+                    StatusCode::BAD_REQUEST,
+                    json!({ "errors": [{ "code": "PaymentDeclined", "message": reason }] }),
+                ),
+                (Ok(_), FinalizeScript { empty: true, .. }) => (StatusCode::OK, Value::Null),
+                (Ok(id), finalize) => {
+                    if finalize.pending {
+                        state.pending.insert(id, 0);
+                    }
+
+                    (StatusCode::OK, finalized(id, finalize))
+                }
+                (Err(_), _) => (StatusCode::NOT_FOUND, Value::Null),
+            }
+        }
+        _ => (StatusCode::NOT_FOUND, Value::Null),
+    };
+
+    (status, body, delay)
+}
+
+fn fixture_file(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+
+    std::fs::read_to_string(&path).unwrap_or_else(|why| panic!("{}: {}", path, why))
+}
+
+fn order(state: &State, id: i64) -> Value {
+    let mut order: Value = serde_json::from_str(ORDER).unwrap();
+    order["id"] = json!(id);
+    order["checkoutOrder"]["id"] = json!(id);
+    order["orderStatus"] = json!(0);
+
+    if let Some(options) = state.shipping.get(&id) {
+        let totals = &mut order["checkoutOrder"];
+        let subtotal = totals["subTotalAmount"].as_f64().unwrap();
+        let shipping = options
+            .iter()
+            .map(|option| option["price"].as_f64().unwrap_or(0.0))
+            .sum::<f64>();
+        totals["totalShippingFee"] = json!(shipping);
+        totals["grandTotal"] = json!(subtotal + shipping);
+    }
+
+    if let Some(currency) = state.currencies.get(&id) {
+        order["checkoutOrder"]["currency"] = json!(currency);
+    }
+
+    if state.intents.contains(&id) {
+        order["checkoutOrder"]["paymentIntentId"] = json!(format!("pi_{}", id));
+    }
+
+    order
+}
+
+/// Serves the card form for a payment intent and creates instruments from it. The form's
+/// session id is derived from the intent, creating an instrument needs the form's CSRF token.
+fn gateway(segments: &[&str], method: Method, query: &str, csrf: Option<&str>) -> Response<Body> {
+    let param = |name: &str| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+
+    match (method, segments) {
+        (Method::GET, []) => match param("paymentIntentId") {
+            Some(intent) => {
+                let html = format!(
+                    r#"<html><body>
+                        <script>var sessionId = 'session_{}';</script>
+                        <form><input type="hidden" name="_csrf" value="{}"/></form>
+                    </body></html>"#,
+                    intent, CSRF
+                );
+
+                Response::builder()
+                    .header("Content-Type", "text/html")
+                    .body(Body::from(html))
+                    .unwrap()
+            }
+            None => respond(StatusCode::BAD_REQUEST, Value::Null),
+        },
+        (Method::POST, ["instruments"]) => match (param("sessionId"), csrf) {
+            (Some(session), Some(CSRF)) => respond(
+                StatusCode::OK,
+                json!({
+                    "id": session.replacen("session_", "instrument_", 1),
+                    "createdAt": "2021-06-01T12:00:00Z",
+                }),
+            ),
+            _ => respond(StatusCode::FORBIDDEN, Value::Null),
+        },
+        _ => respond(StatusCode::NOT_FOUND, Value::Null),
+    }
+}
+
+fn finalized(id: i64, script: &FinalizeScript) -> Value {
+    let mut finalize: Value = serde_json::from_str(FINALIZE).unwrap();
+    finalize["id"] = json!(id);
+    finalize["checkoutOrder"]["id"] = json!(id);
+
+    if let Some(url) = &script.redirect_url {
+        finalize["paymentStatus"] = json!("RedirectShopper");
+        finalize["redirectUrl"] = json!(url);
+    } else if script.pending {
+        finalize["paymentStatus"] = json!("Pending");
+    }
+
+    finalize
+}
+
+fn respond(status: StatusCode, body: Value) -> Response<Body> {
+    let body = match body {
+        Value::Null => Body::empty(),
+        body => Body::from(body.to_string()),
+    };
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .unwrap()
+}
@@ -1,38 +1,229 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
+use crate::address;
+use crate::card;
+use crate::checkout::{CheckoutPolicy, PurchaseLimits};
+use crate::config;
 use crate::country::Country;
+use crate::phone;
+use crate::price::PriceRules;
+use crate::scale::ProductScale;
+use crate::secret::{CardNumber, Cvv};
+use crate::shipping::ShippingPreference;
+use crate::size::{SizePreference, SizeStrategy};
+use crate::storefront::Storefront;
+use crate::vault::Vault;
+use crate::Error;
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Config {
+    /// Run every task without submitting payment.
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub storefronts: HashMap<String, Storefront>,
+    /// Profiles by id, for tasks to reference.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Lists of profile ids a task can reference under one id.
+    #[serde(default)]
+    pub profile_groups: BTreeMap<String, Vec<String>>,
+    /// Encrypted profiles, referenced by alias like the ones in `profiles`.
+    pub vault: Option<PathBuf>,
+    /// Size scales of product `scaleId`s the tool does not know yet.
+    #[serde(default)]
+    pub scales: Vec<ProductScale>,
     pub tasks: Vec<TaskConfig>,
 }
 
+impl Config {
+    pub fn storefront(&self, name: &str) -> Result<Storefront, Error> {
+        self.storefronts
+            .get(name)
+            .cloned()
+            .or_else(|| Storefront::builtin(name))
+            .ok_or_else(|| Error::Unknown(format!("unknown storefront {}", name)))
+    }
+
+    /// Checks every profile and resolves the profiles each task references, reporting all
+    /// problems at once. Phone numbers are normalized to E.164 on the way.
+    pub fn validate(&mut self, vault: Option<&Vault>) -> Result<(), Error> {
+        let mut problems = Vec::new();
+
+        for (id, profile) in &mut self.profiles {
+            if profile.alias.is_empty() {
+                profile.alias = id.clone();
+            }
+
+            for problem in profile.validate() {
+                problems.push(format!("profile={} {}", profile.alias, problem));
+            }
+        }
+
+        for group in self.profile_groups.keys() {
+            if self.profiles.contains_key(group) {
+                problems.push(format!("profileGroups.{} is also a profile id", group));
+            }
+        }
+
+        let (groups, profiles) = (&self.profile_groups, &self.profiles);
+        // Vault profiles are validated once, when the first task resolves them.
+        let mut vaulted = HashMap::new();
+        for (index, task) in self.tasks.iter_mut().enumerate() {
+            let ids = task
+                .profile_ids
+                .iter()
+                .flat_map(|id| match groups.get(id) {
+                    Some(members) => members.iter().collect(),
+                    None => vec![id],
+                })
+                .collect::<Vec<_>>();
+
+            if ids.is_empty() {
+                problems.push(format!("tasks[{}] references no profiles", index));
+            }
+
+            let mut seen = HashSet::new();
+            task.profiles.clear();
+
+            for id in ids.into_iter().filter(|id| seen.insert(*id)) {
+                let vault_profile = vault.and_then(|vault| vault.profile(id));
+
+                match (profiles.get(id), vault_profile) {
+                    (Some(profile), _) => task.profiles.push(profile.clone()),
+                    (None, Some(profile)) => {
+                        let profile = vaulted.entry(id.clone()).or_insert_with(|| {
+                            let mut profile = profile.clone();
+                            if profile.alias.is_empty() {
+                                profile.alias = id.clone();
+                            }
+
+                            for problem in profile.validate() {
+                                problems.push(format!("profile={} {}", profile.alias, problem));
+                            }
+
+                            profile
+                        });
+                        task.profiles.push(profile.clone());
+                    }
+                    (None, None) => {
+                        problems.push(format!("tasks[{}] profile={} does not exist", index, id))
+                    }
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(problems.join("; ")))
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TaskConfig {
     pub product: String,
+    #[serde(default = "default_storefront")]
+    pub storefront: String,
+    #[serde(
+        default = "default_poll_interval_ms",
+        deserialize_with = "config::number"
+    )]
+    pub poll_interval_ms: u64,
+    #[serde(default)]
+    pub retry: CheckoutPolicy,
+    /// Overrides `Config::dry_run` for this task.
+    pub dry_run: Option<bool>,
+    #[serde(default)]
+    pub limits: PurchaseLimits,
+    /// Price a product must have before it is released to the profiles.
+    #[serde(default)]
+    pub price: PriceRules,
+    /// How each profile picks among the sizes it wears.
+    #[serde(default)]
+    pub size_strategy: SizeStrategy,
+    /// Seeds the random size choice, for reproducible runs.
+    pub seed: Option<u64>,
+    /// Ids of profiles or profile groups.
+    #[serde(default, rename = "profiles")]
+    pub profile_ids: Vec<String>,
+    /// The referenced profiles, filled in by `Config::validate`.
+    #[serde(skip)]
     pub profiles: Vec<Profile>,
 }
 
-#[derive(Debug, Deserialize)]
+fn default_storefront() -> String {
+    "emiliopucci".into()
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Profile {
+    /// Name the profile goes by in logs, defaults to its id.
+    #[serde(default)]
+    pub alias: String,
     pub email: String,
     pub phone: String,
     pub card: Card,
     pub delivery: Address,
     pub billing: Address,
+    /// Sizes the profile wears, most wanted first. Empty means any size.
+    #[serde(default)]
+    pub sizes: Vec<SizePreference>,
+    /// Shipping option to pick among those the order offers.
+    #[serde(default)]
+    pub shipping: ShippingPreference,
 }
 
-#[derive(Debug, Deserialize)]
+impl Profile {
+    /// Local phone numbers are read in the delivery country. The phone and card are
+    /// normalized on the way.
+    pub fn validate(&mut self) -> Vec<String> {
+        let delivery = address::validate(&self.delivery)
+            .into_iter()
+            .map(|problem| format!("delivery.{}", problem));
+        let billing = address::validate(&self.billing)
+            .into_iter()
+            .map(|problem| format!("billing.{}", problem));
+        let mut problems = delivery.chain(billing).collect::<Vec<_>>();
+
+        problems.extend(self.card.validate(card::current_month()));
+
+        match phone::normalize(&self.phone, &self.delivery.country) {
+            Some(phone) => self.phone = phone,
+            None => problems.push(format!(
+                "phone=\"{}\" is not a valid {} phone number",
+                self.phone,
+                self.delivery.country.data().fps.name
+            )),
+        }
+
+        problems
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Card {
-    pub number: String,
+    pub number: CardNumber,
+    #[serde(deserialize_with = "config::number")]
     pub expiry_month: i64,
+    #[serde(deserialize_with = "config::number")]
     pub expiry_year: i64,
-    pub cvv: String,
+    pub cvv: Cvv,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Address {
     pub first_name: String,
@@ -70,6 +261,7 @@ pub struct FPSCity<'a> {
 #[serde(rename_all = "camelCase")]
 pub struct FPSState<'a> {
     pub name: &'a str,
+    pub code: &'a str,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -88,6 +280,12 @@ pub struct FPSPatchAddress<'a> {
 
 ///////////////////////
 
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSUser {
+    pub id: i64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FPSProduct {
@@ -170,7 +368,8 @@ pub struct Variant {
 pub struct FPSOrder {
     pub id: i64,
     pub checkout_order: FPSCheckoutOrder,
-    // pub shipping_options: Vec<ShippingOption>,
+    #[serde(default)]
+    pub shipping_options: Vec<FPSShippingOption>,
     pub payment_methods: FPSPaymentMethods,
     pub order_status: i64,
 }
@@ -206,6 +405,29 @@ pub struct FPSCheckoutOrder {
     pub payment_intent_id: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSShippingOption {
+    pub currency: String,
+    pub merchants: Vec<i64>,
+    pub price: f64,
+    pub formatted_price: String,
+    pub shipping_cost_type: i64,
+    pub shipping_service: FPSShippingService,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSShippingService {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub min_estimated_delivery_hour: f64,
+    pub max_estimated_delivery_hour: f64,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FPSPaymentMethods {
@@ -239,6 +461,35 @@ pub struct FPSCreditCard {
     pub code: String,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSFinalize {
+    #[serde(default)]
+    pub checkout_order: Option<FPSFinalizedOrder>,
+    #[serde(default)]
+    pub payment_status: Option<String>,
+    #[serde(default)]
+    pub refusal_reason: Option<String>,
+    #[serde(default)]
+    pub redirect_url: Option<String>,
+    #[serde(default)]
+    pub errors: Vec<FPSError>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSFinalizedOrder {
+    pub order_id: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSError {
+    #[serde(default)]
+    pub code: String,
+    pub message: String,
+}
+
 //////
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -258,14 +509,14 @@ pub struct FPSItem {
     pub variant_id: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FPSCardPaymentIntent<'a> {
-    pub card_number: &'a str,
+    pub card_number: &'a CardNumber,
     pub card_holder_name: &'a str,
     pub card_expiry_month: i64,
     pub card_expiry_year: i64,
-    pub card_cvv: &'a str,
+    pub card_cvv: &'a Cvv,
     pub payment_method_type: &'a str,
     pub payment_method_id: &'a str,
     pub save_payment_method_as_token: bool,
@@ -274,5 +525,125 @@ pub struct FPSCardPaymentIntent<'a> {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FPSCreatedIntent {
+    pub id: String,
     pub created_at: String,
 }
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FPSIntentFinalize<'a> {
+    pub payment_intent_id: &'a str,
+    pub instrument_id: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock;
+    use serde_json::{json, Value};
+
+    fn config(tasks: Value) -> Config {
+        serde_json::from_value(json!({
+            "profileGroups": { "uk": ["jane", "john"] },
+            "tasks": tasks,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_problems_of_every_profile() {
+        let mut invalid = mock::profile(Country::GB);
+        invalid.delivery.zip = "10115".into();
+        invalid.billing.city = "".into();
+        invalid.phone = "7700".into();
+
+        let mut config = config(json!([{ "product": mock::PRODUCT_ID, "profiles": ["uk"] }]));
+        config
+            .profiles
+            .insert("jane".into(), mock::profile(Country::GB));
+        config.profiles.insert("john".into(), invalid);
+
+        assert_eq!(
+            config.validate(None).unwrap_err().to_string(),
+            "invalid_config=profile=john delivery.zip=\"10115\" is not a valid United Kingdom \
+             postal code; profile=john billing.city is required; profile=john phone=\"7700\" is \
+             not a valid United Kingdom phone number"
+        );
+    }
+
+    #[test]
+    fn resolves_profiles_groups_and_vault_aliases() {
+        let mut vault = Vault::default();
+        vault
+            .import([("vaulted".to_string(), mock::profile(Country::GB))].into())
+            .unwrap();
+
+        let mut config = config(json!([
+            { "product": mock::PRODUCT_ID, "profiles": ["uk", "jane", "vaulted"] },
+            { "product": mock::PRODUCT_ID, "profiles": ["john"] },
+        ]));
+        config
+            .profiles
+            .insert("jane".into(), mock::profile(Country::GB));
+        config
+            .profiles
+            .insert("john".into(), mock::profile(Country::GB));
+
+        config.validate(Some(&vault)).unwrap();
+
+        let aliases = |task: &TaskConfig| {
+            task.profiles
+                .iter()
+                .map(|profile| profile.alias.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(aliases(&config.tasks[0]), vec!["jane", "john", "vaulted"]);
+        assert_eq!(aliases(&config.tasks[1]), vec!["john"]);
+    }
+
+    #[test]
+    fn reports_problems_of_vault_profiles_once() {
+        let mut expired = mock::profile(Country::GB);
+        expired.card.expiry_year = 2020;
+        expired.phone = "07700 900000".into();
+
+        let vault = Vault::unchecked([("vaulted".to_string(), expired)].into());
+
+        let mut config = config(json!([
+            { "product": mock::PRODUCT_ID, "profiles": ["vaulted"] },
+            { "product": mock::PRODUCT_ID, "profiles": ["vaulted"] },
+        ]));
+
+        assert_eq!(
+            config.validate(Some(&vault)).unwrap_err().to_string(),
+            "invalid_config=profile=vaulted card expired in 2020-12"
+        );
+        assert_eq!(config.tasks[1].profiles[0].phone, "+447700900000");
+    }
+
+    #[test]
+    fn reports_unresolved_references() {
+        let mut config = config(json!([
+            { "product": mock::PRODUCT_ID, "profiles": ["uk", "vaulted"] },
+            { "product": mock::PRODUCT_ID },
+        ]));
+        config
+            .profiles
+            .insert("jane".into(), mock::profile(Country::GB));
+
+        assert_eq!(
+            config.validate(None).unwrap_err().to_string(),
+            "invalid_config=tasks[0] profile=john does not exist; tasks[0] profile=vaulted does \
+             not exist; tasks[1] references no profiles"
+        );
+    }
+
+    #[test]
+    fn normalizes_phone_numbers() {
+        let mut profile = mock::profile(Country::GB);
+        profile.phone = "07700 900000".into();
+
+        assert!(profile.validate().is_empty());
+        assert_eq!(profile.phone, "+447700900000");
+    }
+}
@@ -1,120 +1,155 @@
+use crate::client::FpsClient;
 use crate::country::Country;
-use crate::model::FPSProduct;
-use crate::model::Variant;
+use crate::model::{FPSProduct, Price, Variant};
+use crate::price::PriceRules;
+use crate::storefront::Storefront;
 use crate::Error;
 use log::info;
 use log::warn;
-use reqwest::header::ACCEPT_LANGUAGE;
-use reqwest::header::{HeaderMap, HeaderValue, HOST, USER_AGENT};
-use reqwest::Client;
-use reqwest::Url;
 use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
-use strum::AsStaticRef;
 use tokio::sync::broadcast::Sender;
 
+/// A product with variants in stock, as broadcast to the tasks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Release {
+    pub product: i64,
+    /// The storefront's id of the scale the variant sizes are written in.
+    pub scale_id: i64,
+    /// The price the product was released at.
+    pub price: Option<Price>,
+    pub variants: Vec<Variant>,
+}
+
+impl From<FPSProduct> for Release {
+    fn from(product: FPSProduct) -> Release {
+        Release {
+            product: product.result.id,
+            scale_id: product.result.scale_id,
+            price: product.price,
+            variants: product.result.variants,
+        }
+    }
+}
+
 pub struct Monitor {
     product: String,
-    client: Client,
-    base_url: Url,
-    sender: Sender<(i64, Vec<Variant>)>,
+    client: FpsClient,
+    interval: Duration,
+    rules: PriceRules,
+    currency: &'static str,
+    sender: Sender<Release>,
 }
 
 impl Monitor {
     pub fn new(
         product: String,
+        storefront: &Storefront,
         country: &Country,
-        sender: Sender<(i64, Vec<Variant>)>,
+        interval: Duration,
+        rules: PriceRules,
+        sender: Sender<Release>,
     ) -> Result<Monitor, Error> {
-        let base_url = Url::parse(country.fps_base_url())?;
-        let mut headers = HeaderMap::new();
-        headers.insert(HOST, HeaderValue::from_static("www.emiliopucci.com:443"));
-        headers.insert(
-            USER_AGENT,
-            HeaderValue::from_static(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0",
-            ),
-        );
-        headers.insert(
-            ACCEPT_LANGUAGE,
-            HeaderValue::from_static(country.accept_language()),
-        );
-        headers.insert("FF-Country", HeaderValue::from_static(country.as_static()));
-        headers.insert(
-            "FF-Currency",
-            HeaderValue::from_static(country.fps_currency()),
-        );
-
-        let client = Client::builder()
-            .use_rustls_tls()
-            .gzip(true)
-            .default_headers(headers)
-            .cookie_store(true)
-            .user_agent("Mozilla/5.0 (iPhone; CPU iPhone OS 14_4_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1")
-            .build()?;
+        let client = FpsClient::new(storefront.clone(), country.clone())?;
 
         Ok(Monitor {
             product,
             client,
-            base_url,
+            interval,
+            rules,
+            currency: country.data().currency,
             sender,
         })
     }
 
     pub async fn start(&mut self) -> Result<(), Error> {
         loop {
-            match self.fetch_product().await {
-                Ok(product) => {
-                    let variants = product
-                        .result
-                        .variants
-                        .iter()
-                        .cloned()
-                        .filter(|variant| variant.quantity > 0)
-                        .collect::<Vec<_>>();
-
-                    if variants.len() > 0 {
-                        info!(
-                            "product={} message=\"variants loaded - {}\"",
-                            &self.product,
-                            variants.len()
-                        );
-                        if let Err(why) = self.sender.send((product.result.id, variants)) {
-                            warn!("{}", why);
-                        }
-                    } else {
-                        warn!("product={} message=\"no variants loaded\"", &self.product)
-                    }
-                }
+            match self.client.get_product(&self.product).await {
+                Ok(product) => match self.rules.check(product.price.as_ref(), self.currency) {
+                    Ok(()) => self.release(product),
+                    Err(why) => warn!(
+                        "product={} message=\"held back by price rules, {}\"",
+                        &self.product, why
+                    ),
+                },
                 Err(why) => {
-                    dbg!(why);
+                    warn!("product={} error=\"{}\"", &self.product, why)
                 }
             }
 
-            let duration = Duration::from_millis(1000);
+            tokio::time::sleep(self.interval).await;
+        }
+    }
 
-            tokio::time::sleep(duration).await;
+    fn release(&self, product: FPSProduct) {
+        let mut release = Release::from(product);
+        release.variants.retain(|variant| variant.quantity > 0);
+
+        if !release.variants.is_empty() {
+            info!(
+                "product={} message=\"variants loaded - {}\"",
+                &self.product,
+                release.variants.len()
+            );
+            if let Err(why) = self.sender.send(release) {
+                warn!("{}", why);
+            }
+        } else {
+            warn!("product={} message=\"no variants loaded\"", &self.product)
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, MockServer};
+    use tokio::sync::broadcast;
 
-    async fn fetch_product(&self) -> Result<FPSProduct, Error> {
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH)?;
-        let query = format!("ts={}", since_the_epoch.as_millis());
+    async fn first_release(rules: PriceRules) -> (MockServer, Option<Release>) {
+        let server = MockServer::start().await;
+        let (sender, mut receiver) = broadcast::channel(32);
+        let mut monitor = Monitor::new(
+            mock::PRODUCT_ID.into(),
+            &server.storefront(),
+            &Country::GB,
+            Duration::from_millis(20),
+            rules,
+            sender,
+        )
+        .unwrap();
+
+        let handle = tokio::spawn(async move { monitor.start().await });
+        let release = tokio::time::timeout(Duration::from_millis(200), receiver.recv())
+            .await
+            .ok()
+            .map(Result::unwrap);
+        handle.abort();
 
-        let mut url = self
-            .base_url
-            .clone()
-            .join("/api/products/")?
-            .join(&self.product)?;
+        (server, release)
+    }
 
-        url.set_query(Some(&query));
+    #[tokio::test]
+    async fn releases_within_price_rules() {
+        let rules = PriceRules {
+            max_price: Some(450.0),
+            ..PriceRules::default()
+        };
+        let (_server, release) = first_release(rules).await;
 
-        let response = self.client.get(url).send().await?.error_for_status()?;
-        let body = response.bytes().await?;
+        let release = release.expect("monitor never released the product");
+        assert_eq!(release.product, 16472289);
+        assert_eq!(release.variants.len(), 1);
+    }
 
-        let product = serde_json::from_slice(&body)?;
+    #[tokio::test]
+    async fn holds_back_releases_breaking_price_rules() {
+        let rules = PriceRules {
+            max_price: Some(400.0),
+            ..PriceRules::default()
+        };
+        let (server, release) = first_release(rules).await;
 
-        Ok(product)
+        assert_eq!(release, None);
+        assert!(server.count(reqwest::Method::GET, "products/16472289") > 1);
     }
 }
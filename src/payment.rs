@@ -0,0 +1,113 @@
+use crate::Error;
+use regex::Regex;
+
+/// Session and CSRF token scraped from the payment gateway's card form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentForm {
+    pub session_id: String,
+    pub csrf: String,
+}
+
+impl PaymentForm {
+    pub fn parse(html: &str) -> Result<PaymentForm, Error> {
+        let session_id =
+            session_id(html).ok_or_else(|| Error::PaymentForm("missing sessionId".into()))?;
+        let csrf = csrf(html).ok_or_else(|| Error::PaymentForm("missing _csrf".into()))?;
+
+        Ok(PaymentForm { session_id, csrf })
+    }
+}
+
+/// Matches `var sessionId = '...'` as well as `sessionId: "..."` in inline config objects.
+fn session_id(html: &str) -> Option<String> {
+    let pattern = Regex::new(
+        r#"(?:\b(?:var|let|const)\s+sessionId\s*=|["']?\bsessionId["']?\s*:)\s*["']([^"']+)["']"#,
+    )
+    .unwrap();
+
+    pattern
+        .captures(html)
+        .map(|captures| captures[1].trim().to_string())
+        .filter(|session| !session.is_empty())
+}
+
+/// Reads `_csrf` from a hidden input's `value` or a meta tag's `content`, whatever the
+/// attribute order or quoting.
+fn csrf(html: &str) -> Option<String> {
+    let tags = Regex::new(r"(?is)<(?:input|meta)\b[^>]*>").unwrap();
+    let attributes = Regex::new(r#"(?s)([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap();
+
+    let csrf = tags.find_iter(html).find_map(|tag| {
+        let mut name = None;
+        let mut value = None;
+
+        for captures in attributes.captures_iter(tag.as_str()) {
+            let attribute = match captures.get(2).or_else(|| captures.get(3)) {
+                Some(attribute) => attribute.as_str(),
+                None => continue,
+            };
+
+            match captures[1].to_ascii_lowercase().as_str() {
+                "name" => name = Some(attribute),
+                "value" | "content" => value = Some(attribute),
+                _ => {}
+            }
+        }
+
+        match (name, value) {
+            (Some("_csrf"), Some(value)) if !value.is_empty() => Some(value.to_string()),
+            _ => None,
+        }
+    });
+
+    csrf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_inline_script_and_hidden_input() {
+        let html = r#"
+            <script>var sessionId = 'a1b2c3';</script>
+            <form><input type="hidden" name="_csrf" value="tok-123"/></form>
+        "#;
+
+        assert_eq!(
+            PaymentForm::parse(html).unwrap(),
+            PaymentForm {
+                session_id: "a1b2c3".into(),
+                csrf: "tok-123".into()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_config_object_and_meta_tag() {
+        let html = r#"
+            <meta content='tok-456' name='_csrf'>
+            <script>window.config = { "sessionId": "d4e5f6", "locale": "en-GB" };</script>
+        "#;
+
+        let form = PaymentForm::parse(html).unwrap();
+        assert_eq!(form.session_id, "d4e5f6");
+        assert_eq!(form.csrf, "tok-456");
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let missing_csrf = "<script>var sessionId = 'a1b2c3';</script>";
+        let missing_session = r#"<input name="_csrf" value="tok">"#;
+
+        assert!(matches!(
+            PaymentForm::parse(missing_csrf),
+            Err(Error::PaymentForm(why)) if why == "missing _csrf"
+        ));
+        assert!(matches!(
+            PaymentForm::parse(missing_session),
+            Err(Error::PaymentForm(why)) if why == "missing sessionId"
+        ));
+        assert!(PaymentForm::parse(r#"<input name="_csrf" value="">"#).is_err());
+    }
+}
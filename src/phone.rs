@@ -0,0 +1,93 @@
+use crate::country::Country;
+
+/// Normalizes `phone` to E.164. Numbers starting with `+` or `00` keep their own country
+/// code, anything else is read as a local number of `country`, so `07700 900000` in GB
+/// becomes `+447700900000`.
+pub fn normalize(phone: &str, country: &Country) -> Option<String> {
+    let phone = phone.trim();
+    let (international, number) = match phone.strip_prefix('+') {
+        Some(number) => (true, number),
+        None => (false, phone),
+    };
+
+    if !number
+        .chars()
+        .all(|c| c.is_ascii_digit() || " -./()".contains(c))
+    {
+        return None;
+    }
+
+    let digits = number
+        .chars()
+        .filter(char::is_ascii_digit)
+        .collect::<String>();
+
+    let digits = match (international, digits.strip_prefix("00")) {
+        (true, _) => digits,
+        (false, Some(digits)) => digits.to_string(),
+        (false, None) => {
+            let national = match trunk_prefix(country) {
+                Some(trunk) => digits.strip_prefix(trunk).unwrap_or(&digits),
+                None => &digits,
+            };
+
+            format!("{}{}", country.data().calling_code, national)
+        }
+    };
+
+    if (8..=15).contains(&digits.len()) && !digits.starts_with('0') {
+        Some(format!("+{}", digits))
+    } else {
+        None
+    }
+}
+
+/// Digit dialled before a local number within the country. Italy, San Marino and the
+/// Vatican keep their leading zero after the country code.
+fn trunk_prefix(country: &Country) -> Option<&'static str> {
+    match country {
+        Country::IT | Country::SM | Country::VA => None,
+        Country::US | Country::CA | Country::PR => Some("1"),
+        Country::RU | Country::BY => Some("8"),
+        _ => Some("0"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_local_formats() {
+        let cases = [
+            ("07700 900000", Country::GB, "+447700900000"),
+            ("+44 7700 900000", Country::GB, "+447700900000"),
+            ("0044-7700-900000", Country::DE, "+447700900000"),
+            ("(415) 555-0100", Country::US, "+14155550100"),
+            ("1-415-555-0100", Country::CA, "+14155550100"),
+            ("030 1234567", Country::DE, "+49301234567"),
+            ("06 1234 5678", Country::IT, "+390612345678"),
+            ("06 12 34 56 78", Country::FR, "+33612345678"),
+            ("8 (912) 345-67-89", Country::RU, "+79123456789"),
+        ];
+
+        for (phone, country, expected) in &cases {
+            assert_eq!(
+                normalize(phone, country).as_deref(),
+                Some(*expected),
+                "{}",
+                phone
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_numbers() {
+        assert_eq!(normalize("", &Country::GB), None);
+        assert_eq!(normalize("call me", &Country::GB), None);
+        assert_eq!(normalize("+44 7700 ext 1", &Country::GB), None);
+        assert_eq!(normalize("123", &Country::GB), None);
+        assert_eq!(normalize("+0 1234 5678", &Country::GB), None);
+        assert_eq!(normalize("+1234567890123456", &Country::US), None);
+    }
+}
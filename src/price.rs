@@ -0,0 +1,280 @@
+use crate::model::{FPSCheckoutOrder, Price};
+use serde::Deserialize;
+
+/// Rounding slack when comparing amounts.
+const CENT: f64 = 0.01;
+
+/// Conditions a product's price must meet before the monitor releases it to the tasks,
+/// checked on every poll so a reprice holds the product back again, and limits on the
+/// order totals the task checks before paying.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PriceRules {
+    /// Highest price including taxes.
+    pub max_price: Option<f64>,
+    /// Currency of every amount here, defaults to the currency of the delivery country.
+    pub currency: Option<String>,
+    /// Lowest discount, in percent.
+    pub min_discount: Option<f64>,
+    /// Only buy while the product is discounted.
+    pub sale_only: bool,
+    /// Highest shipping fee of an order.
+    pub max_shipping: Option<f64>,
+    /// Highest duties and taxes charged on top of the price and shipping.
+    pub max_duties: Option<f64>,
+    /// Highest grand total of an order.
+    pub max_total: Option<f64>,
+}
+
+impl PriceRules {
+    fn holds_back_products(&self) -> bool {
+        self.max_price.is_some() || self.min_discount.is_some() || self.sale_only
+    }
+
+    fn has_order_limits(&self) -> bool {
+        self.max_shipping.is_some() || self.max_duties.is_some() || self.max_total.is_some()
+    }
+
+    /// Checks `price`, quoted in `currency`, returning why it breaks the rules.
+    pub fn check(&self, price: Option<&Price>, currency: &str) -> Result<(), String> {
+        if !self.holds_back_products() {
+            return Ok(());
+        }
+
+        let price = price.ok_or_else(|| "product has no price".to_string())?;
+
+        if let Some(max_price) = self.max_price {
+            let max_currency = self.currency.as_deref().unwrap_or(currency);
+
+            if !max_currency.eq_ignore_ascii_case(currency) {
+                return Err(format!(
+                    "priced in {}, maxPrice is in {}",
+                    currency, max_currency
+                ));
+            }
+
+            if price.price_incl_taxes > max_price {
+                return Err(format!(
+                    "price {} {} is above maxPrice {}",
+                    price.price_incl_taxes, currency, max_price
+                ));
+            }
+        }
+
+        let on_sale = price.discount_rate > 0.0
+            || price.price_incl_taxes < price.price_incl_taxes_without_discount;
+
+        if self.sale_only && !on_sale {
+            return Err("not on sale".into());
+        }
+
+        match self.min_discount {
+            Some(min_discount) if price.discount_rate < min_discount => Err(format!(
+                "discount {}% is below minDiscount {}%",
+                price.discount_rate, min_discount
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the totals of an order about to be paid, quoted in `currency` for a product
+    /// released at `price`, returning every problem.
+    pub fn check_order(
+        &self,
+        order: &FPSCheckoutOrder,
+        price: Option<&Price>,
+        currency: &str,
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if !order.currency.eq_ignore_ascii_case(currency) {
+            problems.push(format!(
+                "order is in {}, expected {}",
+                order.currency, currency
+            ));
+            return problems;
+        }
+
+        if let Some(price) = price {
+            if order.sub_total_amount > price.price_incl_taxes + CENT {
+                problems.push(format!(
+                    "subtotal {} {} is above the released price {}",
+                    order.sub_total_amount, currency, price.price_incl_taxes
+                ));
+            }
+        }
+
+        if !self.has_order_limits() {
+            return problems;
+        }
+
+        let limits_currency = self.currency.as_deref().unwrap_or(currency);
+        if !limits_currency.eq_ignore_ascii_case(currency) {
+            problems.push(format!(
+                "order is in {}, limits are in {}",
+                currency, limits_currency
+            ));
+            return problems;
+        }
+
+        // Discounts and store credit come off the grand total, not off the duties.
+        let duties = order.grand_total + order.total_discount + order.total_credit
+            - order.sub_total_amount
+            - order.total_shipping_fee;
+        let limits = [
+            (
+                "shipping",
+                order.total_shipping_fee,
+                self.max_shipping,
+                "maxShipping",
+            ),
+            ("duties", duties, self.max_duties, "maxDuties"),
+            ("total", order.grand_total, self.max_total, "maxTotal"),
+        ];
+
+        for (name, amount, max, field) in &limits {
+            match max {
+                Some(max) if *amount > max + CENT => problems.push(format!(
+                    "{} {} {} is above {} {}",
+                    name, amount, currency, field, max
+                )),
+                _ => {}
+            }
+        }
+
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(price: f64, without_discount: f64) -> Price {
+        Price {
+            price_incl_taxes: price,
+            price_incl_taxes_without_discount: without_discount,
+            discount_rate: (100.0 * (1.0 - price / without_discount)).round(),
+            ..Price::default()
+        }
+    }
+
+    fn rules(json: &str) -> PriceRules {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn releases_anything_without_rules() {
+        assert_eq!(PriceRules::default().check(None, "EUR"), Ok(()));
+    }
+
+    #[test]
+    fn holds_back_prices_above_the_maximum() {
+        let rules = rules(r#"{ "maxPrice": 400 }"#);
+
+        assert_eq!(rules.check(Some(&price(400.0, 400.0)), "EUR"), Ok(()));
+        assert_eq!(
+            rules.check(Some(&price(450.0, 450.0)), "EUR"),
+            Err("price 450 EUR is above maxPrice 400".into())
+        );
+        assert_eq!(rules.check(None, "EUR"), Err("product has no price".into()));
+    }
+
+    #[test]
+    fn holds_back_prices_in_another_currency() {
+        let rules = rules(r#"{ "maxPrice": 400, "currency": "eur" }"#);
+
+        assert_eq!(rules.check(Some(&price(300.0, 300.0)), "EUR"), Ok(()));
+        assert_eq!(
+            rules.check(Some(&price(300.0, 300.0)), "GBP"),
+            Err("priced in GBP, maxPrice is in eur".into())
+        );
+    }
+
+    #[test]
+    fn requires_a_sale_and_minimum_discount() {
+        let sale_only = rules(r#"{ "saleOnly": true }"#);
+        let discount = rules(r#"{ "minDiscount": 30 }"#);
+
+        assert_eq!(
+            sale_only.check(Some(&price(450.0, 450.0)), "EUR"),
+            Err("not on sale".into())
+        );
+        assert_eq!(sale_only.check(Some(&price(405.0, 450.0)), "EUR"), Ok(()));
+        assert_eq!(
+            discount.check(Some(&price(405.0, 450.0)), "EUR"),
+            Err("discount 10% is below minDiscount 30%".into())
+        );
+        assert_eq!(discount.check(Some(&price(315.0, 450.0)), "EUR"), Ok(()));
+    }
+
+    fn order(currency: &str, subtotal: f64, shipping: f64, total: f64) -> FPSCheckoutOrder {
+        FPSCheckoutOrder {
+            currency: currency.into(),
+            sub_total_amount: subtotal,
+            total_shipping_fee: shipping,
+            grand_total: total,
+            ..FPSCheckoutOrder::default()
+        }
+    }
+
+    #[test]
+    fn accepts_expected_order_totals() {
+        let rules = rules(r#"{ "maxShipping": 20, "maxDuties": 0, "maxTotal": 470 }"#);
+        let order = order("EUR", 450.0, 20.0, 470.0);
+
+        assert!(rules
+            .check_order(&order, Some(&price(450.0, 450.0)), "EUR")
+            .is_empty());
+        assert!(PriceRules::default()
+            .check_order(&order, None, "eur")
+            .is_empty());
+    }
+
+    #[test]
+    fn reports_unexpected_order_totals() {
+        let limits = rules(r#"{ "maxShipping": 20, "maxDuties": 0, "maxTotal": 470 }"#);
+
+        assert_eq!(
+            limits.check_order(&order("USD", 450.0, 20.0, 470.0), None, "EUR"),
+            vec!["order is in USD, expected EUR"]
+        );
+        assert_eq!(
+            limits.check_order(
+                &order("EUR", 480.0, 35.0, 575.0),
+                Some(&price(450.0, 450.0)),
+                "EUR"
+            ),
+            vec![
+                "subtotal 480 EUR is above the released price 450",
+                "shipping 35 EUR is above maxShipping 20",
+                "duties 60 EUR is above maxDuties 0",
+                "total 575 EUR is above maxTotal 470",
+            ]
+        );
+        assert_eq!(
+            rules(r#"{ "maxTotal": 470, "currency": "GBP" }"#).check_order(
+                &order("EUR", 450.0, 20.0, 470.0),
+                None,
+                "EUR"
+            ),
+            vec!["order is in EUR, limits are in GBP"]
+        );
+    }
+
+    #[test]
+    fn adds_discounts_and_credit_back_into_duties() {
+        let limits = rules(r#"{ "maxDuties": 0 }"#);
+        let mut discounted = order("EUR", 450.0, 20.0, 395.0);
+        discounted.total_discount = 45.0;
+        discounted.total_credit = 30.0;
+
+        assert!(limits.check_order(&discounted, None, "EUR").is_empty());
+
+        discounted.grand_total = 420.0;
+        assert_eq!(
+            limits.check_order(&discounted, None, "EUR"),
+            vec!["duties 25 EUR is above maxDuties 0"]
+        );
+    }
+}
@@ -0,0 +1,380 @@
+use crate::country::Country;
+use crate::model::{Address, FPSState};
+
+/// A state, province or other subdivision the storefront knows by name and code.
+#[derive(Debug, PartialEq)]
+pub struct Region {
+    pub code: &'static str,
+    pub name: &'static str,
+    /// Other spellings users write, e.g. `Calif.` for California.
+    pub aliases: &'static [&'static str],
+}
+
+const fn region(
+    code: &'static str,
+    name: &'static str,
+    aliases: &'static [&'static str],
+) -> Region {
+    Region {
+        code,
+        name,
+        aliases,
+    }
+}
+
+#[rustfmt::skip]
+const US: &[Region] = &[
+    region("AL", "Alabama", &["Ala."]),
+    region("AK", "Alaska", &[]),
+    region("AZ", "Arizona", &["Ariz."]),
+    region("AR", "Arkansas", &["Ark."]),
+    region("CA", "California", &["Calif.", "Cal."]),
+    region("CO", "Colorado", &["Colo."]),
+    region("CT", "Connecticut", &["Conn."]),
+    region("DE", "Delaware", &["Del."]),
+    region("DC", "District of Columbia", &["D.C.", "Washington D.C."]),
+    region("FL", "Florida", &["Fla."]),
+    region("GA", "Georgia", &["Ga."]),
+    region("HI", "Hawaii", &[]),
+    region("ID", "Idaho", &[]),
+    region("IL", "Illinois", &["Ill."]),
+    region("IN", "Indiana", &["Ind."]),
+    region("IA", "Iowa", &[]),
+    region("KS", "Kansas", &["Kan.", "Kans."]),
+    region("KY", "Kentucky", &["Ky."]),
+    region("LA", "Louisiana", &["La."]),
+    region("ME", "Maine", &[]),
+    region("MD", "Maryland", &["Md."]),
+    region("MA", "Massachusetts", &["Mass."]),
+    region("MI", "Michigan", &["Mich."]),
+    region("MN", "Minnesota", &["Minn."]),
+    region("MS", "Mississippi", &["Miss."]),
+    region("MO", "Missouri", &["Mo."]),
+    region("MT", "Montana", &["Mont."]),
+    region("NE", "Nebraska", &["Neb.", "Nebr."]),
+    region("NV", "Nevada", &["Nev."]),
+    region("NH", "New Hampshire", &[]),
+    region("NJ", "New Jersey", &[]),
+    region("NM", "New Mexico", &[]),
+    region("NY", "New York", &[]),
+    region("NC", "North Carolina", &[]),
+    region("ND", "North Dakota", &[]),
+    region("OH", "Ohio", &[]),
+    region("OK", "Oklahoma", &["Okla."]),
+    region("OR", "Oregon", &["Ore.", "Oreg."]),
+    region("PA", "Pennsylvania", &["Penn.", "Penna."]),
+    region("RI", "Rhode Island", &[]),
+    region("SC", "South Carolina", &[]),
+    region("SD", "South Dakota", &[]),
+    region("TN", "Tennessee", &["Tenn."]),
+    region("TX", "Texas", &["Tex."]),
+    region("UT", "Utah", &[]),
+    region("VT", "Vermont", &[]),
+    region("VA", "Virginia", &[]),
+    region("WA", "Washington", &["Wash."]),
+    region("WV", "West Virginia", &["W.Va."]),
+    region("WI", "Wisconsin", &["Wis.", "Wisc."]),
+    region("WY", "Wyoming", &["Wyo."]),
+    region("AA", "Armed Forces Americas", &[]),
+    region("AE", "Armed Forces Europe", &[]),
+    region("AP", "Armed Forces Pacific", &[]),
+];
+
+#[rustfmt::skip]
+const CA: &[Region] = &[
+    region("AB", "Alberta", &["Alta."]),
+    region("BC", "British Columbia", &[]),
+    region("MB", "Manitoba", &["Man."]),
+    region("NB", "New Brunswick", &[]),
+    region("NL", "Newfoundland and Labrador", &["Newfoundland", "Nfld."]),
+    region("NS", "Nova Scotia", &[]),
+    region("NT", "Northwest Territories", &["N.W.T."]),
+    region("NU", "Nunavut", &[]),
+    region("ON", "Ontario", &["Ont."]),
+    region("PE", "Prince Edward Island", &["P.E.I."]),
+    region("QC", "Quebec", &["Que.", "PQ"]),
+    region("SK", "Saskatchewan", &["Sask."]),
+    region("YT", "Yukon", &["Y.T."]),
+];
+
+#[rustfmt::skip]
+const IT: &[Region] = &[
+    region("AG", "Agrigento", &[]),
+    region("AL", "Alessandria", &[]),
+    region("AN", "Ancona", &[]),
+    region("AO", "Aosta", &["Valle d'Aosta"]),
+    region("AR", "Arezzo", &[]),
+    region("AP", "Ascoli Piceno", &[]),
+    region("AT", "Asti", &[]),
+    region("AV", "Avellino", &[]),
+    region("BA", "Bari", &[]),
+    region("BT", "Barletta-Andria-Trani", &[]),
+    region("BL", "Belluno", &[]),
+    region("BN", "Benevento", &[]),
+    region("BG", "Bergamo", &[]),
+    region("BI", "Biella", &[]),
+    region("BO", "Bologna", &[]),
+    region("BZ", "Bolzano", &["Bozen"]),
+    region("BS", "Brescia", &[]),
+    region("BR", "Brindisi", &[]),
+    region("CA", "Cagliari", &[]),
+    region("CL", "Caltanissetta", &[]),
+    region("CB", "Campobasso", &[]),
+    region("CE", "Caserta", &[]),
+    region("CT", "Catania", &[]),
+    region("CZ", "Catanzaro", &[]),
+    region("CH", "Chieti", &[]),
+    region("CO", "Como", &[]),
+    region("CS", "Cosenza", &[]),
+    region("CR", "Cremona", &[]),
+    region("KR", "Crotone", &[]),
+    region("CN", "Cuneo", &[]),
+    region("EN", "Enna", &[]),
+    region("FM", "Fermo", &[]),
+    region("FE", "Ferrara", &[]),
+    region("FI", "Firenze", &["Florence"]),
+    region("FG", "Foggia", &[]),
+    region("FC", "Forlì-Cesena", &[]),
+    region("FR", "Frosinone", &[]),
+    region("GE", "Genova", &["Genoa"]),
+    region("GO", "Gorizia", &[]),
+    region("GR", "Grosseto", &[]),
+    region("IM", "Imperia", &[]),
+    region("IS", "Isernia", &[]),
+    region("SP", "La Spezia", &[]),
+    region("AQ", "L'Aquila", &[]),
+    region("LT", "Latina", &[]),
+    region("LE", "Lecce", &[]),
+    region("LC", "Lecco", &[]),
+    region("LI", "Livorno", &[]),
+    region("LO", "Lodi", &[]),
+    region("LU", "Lucca", &[]),
+    region("MC", "Macerata", &[]),
+    region("MN", "Mantova", &["Mantua"]),
+    region("MS", "Massa-Carrara", &[]),
+    region("MT", "Matera", &[]),
+    region("ME", "Messina", &[]),
+    region("MI", "Milano", &["Milan"]),
+    region("MO", "Modena", &[]),
+    region("MB", "Monza e Brianza", &["Monza and Brianza"]),
+    region("NA", "Napoli", &["Naples"]),
+    region("NO", "Novara", &[]),
+    region("NU", "Nuoro", &[]),
+    region("OR", "Oristano", &[]),
+    region("PD", "Padova", &["Padua"]),
+    region("PA", "Palermo", &[]),
+    region("PR", "Parma", &[]),
+    region("PV", "Pavia", &[]),
+    region("PG", "Perugia", &[]),
+    region("PU", "Pesaro e Urbino", &["Pesaro and Urbino"]),
+    region("PE", "Pescara", &[]),
+    region("PC", "Piacenza", &[]),
+    region("PI", "Pisa", &[]),
+    region("PT", "Pistoia", &[]),
+    region("PN", "Pordenone", &[]),
+    region("PZ", "Potenza", &[]),
+    region("PO", "Prato", &[]),
+    region("RG", "Ragusa", &[]),
+    region("RA", "Ravenna", &[]),
+    region("RC", "Reggio Calabria", &["Reggio di Calabria"]),
+    region("RE", "Reggio Emilia", &["Reggio nell'Emilia"]),
+    region("RI", "Rieti", &[]),
+    region("RN", "Rimini", &[]),
+    region("RM", "Roma", &["Rome"]),
+    region("RO", "Rovigo", &[]),
+    region("SA", "Salerno", &[]),
+    region("SS", "Sassari", &[]),
+    region("SV", "Savona", &[]),
+    region("SI", "Siena", &[]),
+    region("SR", "Siracusa", &["Syracuse"]),
+    region("SO", "Sondrio", &[]),
+    region("SU", "Sud Sardegna", &["South Sardinia"]),
+    region("TA", "Taranto", &[]),
+    region("TE", "Teramo", &[]),
+    region("TR", "Terni", &[]),
+    region("TO", "Torino", &["Turin"]),
+    region("TP", "Trapani", &[]),
+    region("TN", "Trento", &[]),
+    region("TV", "Treviso", &[]),
+    region("TS", "Trieste", &[]),
+    region("UD", "Udine", &[]),
+    region("VA", "Varese", &[]),
+    region("VE", "Venezia", &["Venice"]),
+    region("VB", "Verbano-Cusio-Ossola", &[]),
+    region("VC", "Vercelli", &[]),
+    region("VR", "Verona", &[]),
+    region("VV", "Vibo Valentia", &[]),
+    region("VI", "Vicenza", &[]),
+    region("VT", "Viterbo", &[]),
+];
+
+#[rustfmt::skip]
+const ES: &[Region] = &[
+    region("C", "A Coruña", &["La Coruña"]),
+    region("VI", "Álava", &["Araba"]),
+    region("AB", "Albacete", &[]),
+    region("A", "Alicante", &["Alacant"]),
+    region("AL", "Almería", &[]),
+    region("O", "Asturias", &[]),
+    region("AV", "Ávila", &[]),
+    region("BA", "Badajoz", &[]),
+    region("PM", "Baleares", &["Illes Balears", "Islas Baleares", "Balearic Islands"]),
+    region("B", "Barcelona", &[]),
+    region("BI", "Bizkaia", &["Vizcaya"]),
+    region("BU", "Burgos", &[]),
+    region("CC", "Cáceres", &[]),
+    region("CA", "Cádiz", &[]),
+    region("S", "Cantabria", &[]),
+    region("CS", "Castellón", &["Castelló"]),
+    region("CR", "Ciudad Real", &[]),
+    region("CO", "Córdoba", &[]),
+    region("CU", "Cuenca", &[]),
+    region("SS", "Gipuzkoa", &["Guipúzcoa"]),
+    region("GI", "Girona", &["Gerona"]),
+    region("GR", "Granada", &[]),
+    region("GU", "Guadalajara", &[]),
+    region("H", "Huelva", &[]),
+    region("HU", "Huesca", &[]),
+    region("J", "Jaén", &[]),
+    region("LO", "La Rioja", &[]),
+    region("GC", "Las Palmas", &[]),
+    region("LE", "León", &[]),
+    region("L", "Lleida", &["Lérida"]),
+    region("LU", "Lugo", &[]),
+    region("M", "Madrid", &[]),
+    region("MA", "Málaga", &[]),
+    region("MU", "Murcia", &[]),
+    region("NA", "Navarra", &["Nafarroa"]),
+    region("OR", "Ourense", &["Orense"]),
+    region("P", "Palencia", &[]),
+    region("PO", "Pontevedra", &[]),
+    region("SA", "Salamanca", &[]),
+    region("TF", "Santa Cruz de Tenerife", &["Tenerife"]),
+    region("SG", "Segovia", &[]),
+    region("SE", "Sevilla", &["Seville"]),
+    region("SO", "Soria", &[]),
+    region("T", "Tarragona", &[]),
+    region("TE", "Teruel", &[]),
+    region("TO", "Toledo", &[]),
+    region("V", "Valencia", &["València"]),
+    region("VA", "Valladolid", &[]),
+    region("ZA", "Zamora", &[]),
+    region("Z", "Zaragoza", &[]),
+    region("CE", "Ceuta", &[]),
+    region("ML", "Melilla", &[]),
+];
+
+/// Regions the storefront recognizes in `country`, empty where it takes free text.
+pub fn catalog(country: &Country) -> &'static [Region] {
+    match country {
+        Country::US => US,
+        Country::CA => CA,
+        Country::IT => IT,
+        Country::ES => ES,
+        _ => &[],
+    }
+}
+
+/// Finds the region `input` refers to by code, name or alias, ignoring case, accents,
+/// punctuation and spacing, so `CA`, `california` and `Calif.` are all California.
+pub fn find(country: &Country, input: &str) -> Option<&'static Region> {
+    let input = normalize(input);
+    if input.is_empty() {
+        return None;
+    }
+
+    catalog(country).iter().find(|region| {
+        normalize(region.code) == input
+            || normalize(region.name) == input
+            || region.aliases.iter().any(|alias| normalize(alias) == input)
+    })
+}
+
+/// The state `address` is sent with: the catalog's name and code where the country has a
+/// catalog, the text as written everywhere else.
+pub fn fps_state(address: &Address) -> FPSState<'_> {
+    let state = address.state.as_deref().unwrap_or_default().trim();
+
+    match find(&address.country, state) {
+        Some(region) => FPSState {
+            name: region.name,
+            code: region.code,
+        },
+        None => FPSState {
+            name: state,
+            code: "",
+        },
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ä' => 'a',
+            'è' | 'é' | 'ê' | 'ë' => 'e',
+            'ì' | 'í' | 'î' | 'ï' => 'i',
+            'ò' | 'ó' | 'ô' | 'ö' => 'o',
+            'ù' | 'ú' | 'û' | 'ü' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            c => c,
+        })
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn finds_regions_by_code_name_and_alias() {
+        for input in &["CA", "ca", "california", "Calif.", " CALIFORNIA "] {
+            assert_eq!(find(&Country::US, input).unwrap().code, "CA", "{}", input);
+        }
+
+        assert_eq!(find(&Country::CA, "Québec").unwrap().code, "QC");
+        assert_eq!(find(&Country::CA, "P.E.I.").unwrap().code, "PE");
+        assert_eq!(find(&Country::IT, "Milan").unwrap().name, "Milano");
+        assert_eq!(find(&Country::IT, "forli cesena").unwrap().code, "FC");
+        assert_eq!(find(&Country::ES, "Cadiz").unwrap().name, "Cádiz");
+        assert_eq!(find(&Country::ES, "Islas Baleares").unwrap().code, "PM");
+    }
+
+    #[test]
+    fn rejects_unknown_regions() {
+        assert_eq!(find(&Country::US, "Ontario"), None);
+        assert_eq!(find(&Country::CA, "NY"), None);
+        assert_eq!(find(&Country::US, ""), None);
+        assert_eq!(find(&Country::GB, "London"), None);
+    }
+
+    #[test]
+    fn spellings_are_unambiguous() {
+        for country in Country::iter() {
+            let mut seen = HashSet::new();
+
+            for region in catalog(&country) {
+                let spellings = region
+                    .aliases
+                    .iter()
+                    .chain([region.code, region.name].iter())
+                    .map(|spelling| normalize(spelling))
+                    .collect::<HashSet<_>>();
+
+                for spelling in spellings {
+                    assert!(
+                        seen.insert(spelling.clone()),
+                        "{:?}: {} is ambiguous",
+                        country,
+                        spelling
+                    );
+                }
+            }
+        }
+    }
+}
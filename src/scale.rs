@@ -0,0 +1,236 @@
+//! Size scales. A variant's size is written in the scale of its product, given by the
+//! product's `scaleId`, so `42` may be an Italian dress or a European shoe. Sizes of the
+//! scales below, or of the ones `config.json` adds under `scales`, convert through the
+//! charts of their category.
+
+use crate::size::LETTERS;
+use serde::{de, Deserialize, Deserializer};
+use std::str::FromStr;
+use strum::{Display, EnumString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Category {
+    WomenShoes,
+    MenShoes,
+    WomenClothing,
+    MenClothing,
+    /// Letter sizes, `S` or `XL`, which read the same in every scale.
+    Letters,
+}
+
+/// Sizing system, as written before a size in a preference, e.g. `US 9`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Scale {
+    IT,
+    EU,
+    FR,
+    US,
+    UK,
+}
+
+impl<'de> Deserialize<'de> for Scale {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Scale, D::Error> {
+        let text = String::deserialize(deserializer)?;
+
+        Scale::from_str(&text).map_err(|_| de::Error::custom(format!("unknown scale `{}`", text)))
+    }
+}
+
+/// Scale of the sizes of a product, e.g. `{ "id": 17, "category": "menShoes", "scale": "EU" }`
+/// in `config.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ProductScale {
+    pub id: i64,
+    pub category: Category,
+    pub scale: Scale,
+}
+
+impl ProductScale {
+    /// The scale with the storefront's `scaleId` among the `configured` ones and the ones
+    /// listed here, if it is one we can convert.
+    pub fn of(id: i64, configured: &[ProductScale]) -> Option<&ProductScale> {
+        configured.iter().chain(SCALES).find(|scale| scale.id == id)
+    }
+
+    /// Converts `size`, written in this scale, to `scale`.
+    pub fn convert(&self, size: &str, scale: Scale) -> Option<&'static str> {
+        if self.category == Category::Letters {
+            return LETTERS
+                .iter()
+                .find(|letter| letter.eq_ignore_ascii_case(size.trim()))
+                .copied();
+        }
+
+        let size = number(size)?;
+
+        CHARTS
+            .iter()
+            .filter(|row| row.category == self.category)
+            .find(|row| number(row.sizes[self.scale as usize]) == Some(size))
+            .map(|row| row.sizes[scale as usize])
+    }
+}
+
+fn number(size: &str) -> Option<f64> {
+    size.trim().replace(',', ".").parse().ok()
+}
+
+/// Scale ids seen in storefront product payloads, each with the payload it was read from.
+/// The storefront does not document its ids, so a scale is only listed once a product sized
+/// in it has been captured. Until then `config.json` maps the ids of its products under
+/// `scales`; products of other scales only match sizes without a scale.
+#[rustfmt::skip]
+const SCALES: &[ProductScale] = &[
+    // tests/fixtures/product.json, Emilio Pucci silk scarf 16472289, sized S and M. The
+    // scale of a letter-sized product makes no difference to its sizes.
+    scale(206, Category::Letters, Scale::IT),
+];
+
+const fn scale(id: i64, category: Category, scale: Scale) -> ProductScale {
+    ProductScale {
+        id,
+        category,
+        scale,
+    }
+}
+
+/// One size in every scale, in the order of `Scale`.
+struct SizeRow {
+    category: Category,
+    sizes: [&'static str; 5],
+}
+
+#[rustfmt::skip]
+const CHARTS: &[SizeRow] = &[
+    //   category                   IT      EU      FR      US      UK
+    row(Category::WomenShoes,    ["35",   "35",   "35",   "5",    "2"]),
+    row(Category::WomenShoes,    ["35.5", "35.5", "35.5", "5.5",  "2.5"]),
+    row(Category::WomenShoes,    ["36",   "36",   "36",   "6",    "3"]),
+    row(Category::WomenShoes,    ["36.5", "36.5", "36.5", "6.5",  "3.5"]),
+    row(Category::WomenShoes,    ["37",   "37",   "37",   "7",    "4"]),
+    row(Category::WomenShoes,    ["37.5", "37.5", "37.5", "7.5",  "4.5"]),
+    row(Category::WomenShoes,    ["38",   "38",   "38",   "8",    "5"]),
+    row(Category::WomenShoes,    ["38.5", "38.5", "38.5", "8.5",  "5.5"]),
+    row(Category::WomenShoes,    ["39",   "39",   "39",   "9",    "6"]),
+    row(Category::WomenShoes,    ["39.5", "39.5", "39.5", "9.5",  "6.5"]),
+    row(Category::WomenShoes,    ["40",   "40",   "40",   "10",   "7"]),
+    row(Category::WomenShoes,    ["40.5", "40.5", "40.5", "10.5", "7.5"]),
+    row(Category::WomenShoes,    ["41",   "41",   "41",   "11",   "8"]),
+    row(Category::WomenShoes,    ["42",   "42",   "42",   "12",   "9"]),
+    row(Category::MenShoes,      ["39",   "39",   "39",   "6",    "5"]),
+    row(Category::MenShoes,      ["40",   "40",   "40",   "7",    "6"]),
+    row(Category::MenShoes,      ["40.5", "40.5", "40.5", "7.5",  "6.5"]),
+    row(Category::MenShoes,      ["41",   "41",   "41",   "8",    "7"]),
+    row(Category::MenShoes,      ["41.5", "41.5", "41.5", "8.5",  "7.5"]),
+    row(Category::MenShoes,      ["42",   "42",   "42",   "9",    "8"]),
+    row(Category::MenShoes,      ["42.5", "42.5", "42.5", "9.5",  "8.5"]),
+    row(Category::MenShoes,      ["43",   "43",   "43",   "10",   "9"]),
+    row(Category::MenShoes,      ["43.5", "43.5", "43.5", "10.5", "9.5"]),
+    row(Category::MenShoes,      ["44",   "44",   "44",   "11",   "10"]),
+    row(Category::MenShoes,      ["44.5", "44.5", "44.5", "11.5", "10.5"]),
+    row(Category::MenShoes,      ["45",   "45",   "45",   "12",   "11"]),
+    row(Category::MenShoes,      ["46",   "46",   "46",   "13",   "12"]),
+    row(Category::MenShoes,      ["47",   "47",   "47",   "14",   "13"]),
+    row(Category::WomenClothing, ["36",   "30",   "32",   "0",    "4"]),
+    row(Category::WomenClothing, ["38",   "32",   "34",   "2",    "6"]),
+    row(Category::WomenClothing, ["40",   "34",   "36",   "4",    "8"]),
+    row(Category::WomenClothing, ["42",   "36",   "38",   "6",    "10"]),
+    row(Category::WomenClothing, ["44",   "38",   "40",   "8",    "12"]),
+    row(Category::WomenClothing, ["46",   "40",   "42",   "10",   "14"]),
+    row(Category::WomenClothing, ["48",   "42",   "44",   "12",   "16"]),
+    row(Category::WomenClothing, ["50",   "44",   "46",   "14",   "18"]),
+    row(Category::MenClothing,   ["44",   "44",   "44",   "34",   "34"]),
+    row(Category::MenClothing,   ["46",   "46",   "46",   "36",   "36"]),
+    row(Category::MenClothing,   ["48",   "48",   "48",   "38",   "38"]),
+    row(Category::MenClothing,   ["50",   "50",   "50",   "40",   "40"]),
+    row(Category::MenClothing,   ["52",   "52",   "52",   "42",   "42"]),
+    row(Category::MenClothing,   ["54",   "54",   "54",   "44",   "44"]),
+    row(Category::MenClothing,   ["56",   "56",   "56",   "46",   "46"]),
+];
+
+const fn row(category: Category, sizes: [&'static str; 5]) -> SizeRow {
+    SizeRow { category, sizes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_scales() {
+        let men_eu = scale(0, Category::MenShoes, Scale::EU);
+        let women_it = scale(0, Category::WomenClothing, Scale::IT);
+        let women_us = scale(0, Category::WomenShoes, Scale::US);
+
+        assert_eq!(men_eu.convert("42", Scale::US), Some("9"));
+        assert_eq!(men_eu.convert("42,5", Scale::UK), Some("8.5"));
+        assert_eq!(men_eu.convert("42", Scale::EU), Some("42"));
+        assert_eq!(women_it.convert("42", Scale::FR), Some("38"));
+        assert_eq!(women_it.convert("42", Scale::US), Some("6"));
+        assert_eq!(women_us.convert("9", Scale::EU), Some("39"));
+        assert_eq!(men_eu.convert("52", Scale::US), None);
+        assert_eq!(men_eu.convert("M", Scale::US), None);
+    }
+
+    #[test]
+    fn prefers_configured_scales() {
+        let configured: Vec<ProductScale> = serde_json::from_str(
+            r#"[{ "id": 17, "category": "menShoes", "scale": "eu" },
+                { "id": 206, "category": "womenClothing", "scale": "IT" }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            ProductScale::of(17, &configured),
+            Some(&scale(17, Category::MenShoes, Scale::EU))
+        );
+        assert_eq!(
+            ProductScale::of(206, &configured).unwrap().category,
+            Category::WomenClothing
+        );
+        assert!(ProductScale::of(18, &configured).is_none());
+        assert!(serde_json::from_str::<Scale>(r#""JP""#).is_err());
+    }
+
+    #[test]
+    fn keeps_letter_sizes_in_every_scale() {
+        let letters = ProductScale::of(206, &[]).unwrap();
+
+        assert_eq!(letters.convert("s", Scale::US), Some("S"));
+        assert_eq!(letters.convert("XL", Scale::UK), Some("XL"));
+        assert_eq!(letters.convert("42", Scale::EU), None);
+        assert!(ProductScale::of(13, &[]).is_none());
+    }
+
+    #[test]
+    fn charts_are_complete_and_ordered() {
+        for category in &[
+            Category::WomenShoes,
+            Category::MenShoes,
+            Category::WomenClothing,
+            Category::MenClothing,
+        ] {
+            let rows = CHARTS
+                .iter()
+                .filter(|row| row.category == *category)
+                .collect::<Vec<_>>();
+
+            for pair in rows.windows(2) {
+                for (index, (smaller, larger)) in
+                    pair[0].sizes.iter().zip(&pair[1].sizes).enumerate()
+                {
+                    assert!(
+                        number(smaller) < number(larger),
+                        "{:?} column {} {} !< {}",
+                        category,
+                        index,
+                        smaller,
+                        larger
+                    );
+                }
+            }
+        }
+    }
+}
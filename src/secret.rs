@@ -0,0 +1,132 @@
+//! Card data that must not end up in logs. `Debug` and `Display` are masked, the value is
+//! only reachable through `expose` and is zeroized when dropped.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A card number, shown as its last four digits.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CardNumber(String);
+
+impl CardNumber {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn last4(&self) -> &str {
+        let digits = self.0.trim_end();
+        let start = digits
+            .char_indices()
+            .rev()
+            .nth(3)
+            .map_or(0, |(index, _)| index);
+
+        &digits[start..]
+    }
+}
+
+impl fmt::Display for CardNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "****{}", self.last4())
+    }
+}
+
+impl fmt::Debug for CardNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CardNumber({})", self)
+    }
+}
+
+impl From<&str> for CardNumber {
+    fn from(number: &str) -> CardNumber {
+        CardNumber(number.into())
+    }
+}
+
+impl From<String> for CardNumber {
+    fn from(number: String) -> CardNumber {
+        CardNumber(number)
+    }
+}
+
+impl Drop for CardNumber {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// A card verification code, never shown.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Cvv(String);
+
+impl Cvv {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Cvv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "***")
+    }
+}
+
+impl fmt::Debug for Cvv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Cvv(***)")
+    }
+}
+
+impl From<&str> for Cvv {
+    fn from(cvv: &str) -> Cvv {
+        Cvv(cvv.into())
+    }
+}
+
+impl Drop for Cvv {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::country::Country;
+    use crate::mock;
+
+    #[test]
+    fn masks_card_data() {
+        let number = CardNumber::from("4111111111111111");
+        let cvv = Cvv::from("123");
+
+        assert_eq!(number.to_string(), "****1111");
+        assert_eq!(format!("{:?}", number), "CardNumber(****1111)");
+        assert_eq!(cvv.to_string(), "***");
+        assert_eq!(number.expose(), "4111111111111111");
+        assert_eq!(CardNumber::from("12").last4(), "12");
+    }
+
+    #[test]
+    fn profiles_debug_without_card_data() {
+        let profile = mock::profile(Country::GB);
+        let debug = format!("{:?}", profile);
+
+        assert!(!debug.contains("4111111111111111"));
+        assert!(!debug.contains("\"123\""));
+        assert!(debug.contains("****1111"));
+    }
+
+    #[test]
+    fn serializes_plain_values() {
+        let number: CardNumber = serde_json::from_str("\"4111111111111111\"").unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&number).unwrap(),
+            "\"4111111111111111\""
+        );
+    }
+}
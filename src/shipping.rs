@@ -0,0 +1,202 @@
+use crate::model::FPSShippingOption;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Which shipping option a profile takes: `"cheapest"`, `"fastest"` or the name of a
+/// shipping service, e.g. `"Express"`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ShippingPreference {
+    #[default]
+    Cheapest,
+    /// Earliest latest delivery estimate, the cheaper of equally fast options.
+    Fastest,
+    Named(String),
+}
+
+impl ShippingPreference {
+    /// Picks one option for every group of merchants the options are offered to, in the
+    /// order the groups are first offered, or says why a group has none that fits.
+    pub fn select_each<'a>(
+        &self,
+        options: &'a [FPSShippingOption],
+    ) -> Result<Vec<&'a FPSShippingOption>, String> {
+        let mut groups: Vec<&[i64]> = Vec::new();
+        for option in options {
+            if !groups.contains(&option.merchants.as_slice()) {
+                groups.push(&option.merchants);
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|merchants| {
+                self.select(
+                    options
+                        .iter()
+                        .filter(|option| option.merchants == merchants)
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// Picks the option for this preference, or says why none fits.
+    fn select<'a>(
+        &self,
+        options: Vec<&'a FPSShippingOption>,
+    ) -> Result<&'a FPSShippingOption, String> {
+        let by_price = |a: &&FPSShippingOption, b: &&FPSShippingOption| a.price.total_cmp(&b.price);
+
+        let option = match self {
+            ShippingPreference::Cheapest => options.iter().copied().min_by(by_price),
+            ShippingPreference::Fastest => options.iter().copied().min_by(|a, b| {
+                let a_hours = a.shipping_service.max_estimated_delivery_hour;
+                let b_hours = b.shipping_service.max_estimated_delivery_hour;
+
+                a_hours.total_cmp(&b_hours).then_with(|| by_price(a, b))
+            }),
+            ShippingPreference::Named(name) => options
+                .iter()
+                .copied()
+                .find(|option| option.shipping_service.name.eq_ignore_ascii_case(name)),
+        };
+
+        option.ok_or_else(|| {
+            let offered = options
+                .iter()
+                .map(|option| option.shipping_service.name.as_str())
+                .collect::<Vec<_>>();
+
+            format!("{} is not offered, offered: {}", self, offered.join(", "))
+        })
+    }
+}
+
+impl FromStr for ShippingPreference {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<ShippingPreference, String> {
+        let text = text.trim();
+
+        if text.eq_ignore_ascii_case("cheapest") {
+            Ok(ShippingPreference::Cheapest)
+        } else if text.eq_ignore_ascii_case("fastest") {
+            Ok(ShippingPreference::Fastest)
+        } else if text.is_empty() {
+            Err("shipping must not be empty".into())
+        } else {
+            Ok(ShippingPreference::Named(text.into()))
+        }
+    }
+}
+
+impl fmt::Display for ShippingPreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShippingPreference::Cheapest => write!(f, "cheapest"),
+            ShippingPreference::Fastest => write!(f, "fastest"),
+            ShippingPreference::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ShippingPreference {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ShippingPreference, D::Error> {
+        let text = String::deserialize(deserializer)?;
+
+        ShippingPreference::from_str(&text).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for ShippingPreference {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::FPSShippingService;
+
+    fn option(name: &str, price: f64, max_hours: f64) -> FPSShippingOption {
+        FPSShippingOption {
+            merchants: vec![11554],
+            price,
+            shipping_service: FPSShippingService {
+                name: name.into(),
+                max_estimated_delivery_hour: max_hours,
+                ..FPSShippingService::default()
+            },
+            ..FPSShippingOption::default()
+        }
+    }
+
+    fn pick(preference: &str, options: &[FPSShippingOption]) -> Result<String, String> {
+        let preference: ShippingPreference = serde_json::from_str(preference).unwrap();
+
+        preference.select_each(options).map(|picked| {
+            picked
+                .iter()
+                .map(|option| option.shipping_service.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+
+    #[test]
+    fn selects_by_preference() {
+        let options = [
+            option("Standard", 15.0, 120.0),
+            option("Express", 25.0, 48.0),
+            option("Courier", 40.0, 48.0),
+            option("Economy", 15.0, 240.0),
+        ];
+
+        assert_eq!(pick(r#""cheapest""#, &options), Ok("Standard".into()));
+        assert_eq!(pick(r#""Fastest""#, &options), Ok("Express".into()));
+        assert_eq!(pick(r#""courier""#, &options), Ok("Courier".into()));
+        assert_eq!(
+            pick(r#""Same Day""#, &options),
+            Err("Same Day is not offered, offered: Standard, Express, Courier, Economy".into())
+        );
+    }
+
+    #[test]
+    fn selects_for_every_merchant_group() {
+        let mut options = vec![
+            option("Standard", 15.0, 120.0),
+            option("Express", 25.0, 48.0),
+            option("Standard", 10.0, 96.0),
+            option("Courier", 30.0, 24.0),
+        ];
+        options[2].merchants = vec![9359];
+        options[3].merchants = vec![9359];
+
+        assert_eq!(
+            pick(r#""cheapest""#, &options),
+            Ok("Standard, Standard".into())
+        );
+        assert_eq!(
+            pick(r#""fastest""#, &options),
+            Ok("Express, Courier".into())
+        );
+        assert_eq!(
+            pick(r#""Express""#, &options),
+            Err("Express is not offered, offered: Standard, Courier".into())
+        );
+    }
+
+    #[test]
+    fn round_trips_preferences() {
+        let preferences: Vec<ShippingPreference> =
+            serde_json::from_str(r#"["CHEAPEST", "fastest", "Express"]"#).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&preferences).unwrap(),
+            r#"["cheapest","fastest","Express"]"#
+        );
+        assert!(serde_json::from_str::<ShippingPreference>(r#""""#).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//! Which variant a profile buys. A profile lists the sizes it wears in priority order and
+//! the task's `SizeStrategy` picks among the in-stock variants that match any of them.
+//! A size may name its scale, `US 9`, to match whatever scale the product is sized in.
+
+use crate::model::Variant;
+use crate::scale::{ProductScale, Scale};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+
+/// Letter sizes from smallest to largest, so `XS-L` can be read as a range.
+pub const LETTERS: &[&str] = &["XXXS", "XXS", "XS", "S", "M", "L", "XL", "XXL", "XXXL"];
+
+/// Position of a size on its scale. A range's bounds are of the same kind, so a range of
+/// one kind never contains a size of the other.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Rank {
+    Number(f64),
+    Letter(usize),
+}
+
+impl Rank {
+    fn of(size: &str) -> Option<Rank> {
+        let size = size.trim();
+
+        if let Some(index) = LETTERS
+            .iter()
+            .position(|letter| letter.eq_ignore_ascii_case(size))
+        {
+            return Some(Rank::Letter(index));
+        }
+
+        size.replace(',', ".").parse().ok().map(Rank::Number)
+    }
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rank::Number(number) => write!(f, "{}", number),
+            Rank::Letter(index) => write!(f, "{}", LETTERS[*index]),
+        }
+    }
+}
+
+/// One entry of a profile's `sizes`: `"42"`, `"40-44"`, `"S-L"` or `"any"`, optionally
+/// in a scale, `"US 9"` or `"UK 8-9"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizePreference {
+    Exact(String),
+    Range(Rank, Rank),
+    Any,
+    Scaled(Scale, Box<SizePreference>),
+}
+
+impl SizePreference {
+    /// Whether a variant of `size` fits. Sizes without a scale are compared as written,
+    /// sizes with one only match products whose scale converts to it.
+    pub fn matches(&self, size: &str, scale: Option<&ProductScale>) -> bool {
+        match self {
+            SizePreference::Scaled(to, preference) => scale
+                .and_then(|scale| scale.convert(size, *to))
+                .is_some_and(|size| preference.matches(size, None)),
+            SizePreference::Exact(exact) => {
+                exact.eq_ignore_ascii_case(size.trim())
+                    || Rank::of(exact).is_some_and(|rank| Rank::of(size) == Some(rank))
+            }
+            SizePreference::Range(from, to) => {
+                Rank::of(size).is_some_and(|rank| *from <= rank && rank <= *to)
+            }
+            SizePreference::Any => true,
+        }
+    }
+}
+
+impl FromStr for SizePreference {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<SizePreference, String> {
+        let text = text.trim();
+
+        if text.is_empty() {
+            return Err("size must not be empty".into());
+        }
+
+        if text.eq_ignore_ascii_case("any") {
+            return Ok(SizePreference::Any);
+        }
+
+        let scaled = text
+            .split_once(' ')
+            .and_then(|(scale, size)| Some((Scale::from_str(scale).ok()?, size)));
+        if let Some((scale, size)) = scaled {
+            return match SizePreference::from_str(size)? {
+                SizePreference::Any | SizePreference::Scaled(..) => {
+                    Err(format!("size `{}` is not a size in {}", text, scale))
+                }
+                size => Ok(SizePreference::Scaled(scale, Box::new(size))),
+            };
+        }
+
+        let bounds = text
+            .split_once('-')
+            .and_then(|(from, to)| Some((Rank::of(from)?, Rank::of(to)?)));
+
+        match bounds {
+            Some((from, to)) if mem::discriminant(&from) != mem::discriminant(&to) => {
+                Err(format!("size range `{}` mixes numbers and letters", text))
+            }
+            Some((from, to)) if from <= to => Ok(SizePreference::Range(from, to)),
+            Some(_) => Err(format!("size range `{}` is reversed", text)),
+            None => Ok(SizePreference::Exact(text.into())),
+        }
+    }
+}
+
+impl fmt::Display for SizePreference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SizePreference::Exact(size) => write!(f, "{}", size),
+            SizePreference::Range(from, to) => write!(f, "{}-{}", from, to),
+            SizePreference::Any => write!(f, "any"),
+            SizePreference::Scaled(scale, size) => write!(f, "{} {}", scale, size),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SizePreference {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SizePreference, D::Error> {
+        let text = String::deserialize(deserializer)?;
+
+        SizePreference::from_str(&text).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for SizePreference {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// How a task picks among the in-stock variants a profile would wear.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SizeStrategy {
+    /// The first preference that is in stock.
+    #[default]
+    Priority,
+    /// The preferred variant with the most units left, likelier to survive the checkout.
+    MostStock,
+    /// Any preferred variant, spreading profiles of a task over sizes.
+    Random,
+}
+
+/// Picks the variant to buy, or `None` when no preferred size is in stock. No preferences
+/// at all means any size.
+pub fn select<'a, R: Rng>(
+    variants: &'a [Variant],
+    scale: Option<&ProductScale>,
+    sizes: &[SizePreference],
+    strategy: SizeStrategy,
+    rng: &mut R,
+) -> Option<&'a Variant> {
+    let any = [SizePreference::Any];
+    let sizes = if sizes.is_empty() { &any[..] } else { sizes };
+
+    let mut preferred: Vec<&Variant> = Vec::new();
+    for size in sizes {
+        for variant in variants {
+            let candidate = variant.quantity > 0 && size.matches(&variant.size, scale);
+
+            if candidate && !preferred.iter().any(|other| other.id == variant.id) {
+                preferred.push(variant);
+            }
+        }
+    }
+
+    match strategy {
+        SizeStrategy::Priority => preferred.first().copied(),
+        SizeStrategy::MostStock => preferred
+            .iter()
+            .copied()
+            .min_by_key(|variant| std::cmp::Reverse(variant.quantity)),
+        SizeStrategy::Random => preferred.choose(rng).copied(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scale::Category;
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    fn variants(sizes: &[(&str, i64)]) -> Vec<Variant> {
+        sizes
+            .iter()
+            .map(|(size, quantity)| Variant {
+                id: format!("variant-{}", size),
+                size: size.to_string(),
+                quantity: *quantity,
+                ..Variant::default()
+            })
+            .collect()
+    }
+
+    fn sizes(json: &str) -> Vec<SizePreference> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn pick(
+        variants: &[Variant],
+        preferences: &[SizePreference],
+        strategy: SizeStrategy,
+    ) -> Option<String> {
+        let mut rng = SmallRng::seed_from_u64(7);
+
+        select(variants, None, preferences, strategy, &mut rng).map(|variant| variant.size.clone())
+    }
+
+    #[test]
+    fn parses_preferences() {
+        assert_eq!(
+            sizes(r#"["42", "40-44", "xs-l", "ANY", "one size"]"#),
+            vec![
+                SizePreference::Exact("42".into()),
+                SizePreference::Range(Rank::Number(40.0), Rank::Number(44.0)),
+                SizePreference::Range(Rank::Letter(2), Rank::Letter(5)),
+                SizePreference::Any,
+                SizePreference::Exact("one size".into()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&sizes(r#"["42", "40-44.5", "xs-l"]"#)).unwrap(),
+            r#"["42","40-44.5","XS-L"]"#
+        );
+
+        for bad in &[r#"[""]"#, r#"["44-40"]"#, r#"["S-44"]"#, r#"["40-XL"]"#] {
+            assert!(
+                serde_json::from_str::<Vec<SizePreference>>(bad).is_err(),
+                "{}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn matches_sizes() {
+        let range = SizePreference::from_str("40-42").unwrap();
+        let letters = SizePreference::from_str("S-L").unwrap();
+
+        assert!(range.matches("41,5", None));
+        assert!(!range.matches("43", None));
+        assert!(!range.matches("M", None));
+        assert!(letters.matches("m", None));
+        assert!(!letters.matches("XL", None));
+        assert!(!letters.matches("41", None));
+        assert!(SizePreference::from_str("42.0")
+            .unwrap()
+            .matches("42", None));
+    }
+
+    #[test]
+    fn priority_takes_the_first_preference_in_stock() {
+        let variants = variants(&[("40", 5), ("42", 0), ("43", 1), ("44", 9)]);
+
+        assert_eq!(
+            pick(
+                &variants,
+                &sizes(r#"["42", "43", "any"]"#),
+                SizeStrategy::Priority
+            ),
+            Some("43".into())
+        );
+        assert_eq!(
+            pick(
+                &variants,
+                &sizes(r#"["42", "any"]"#),
+                SizeStrategy::Priority
+            ),
+            Some("40".into())
+        );
+        assert_eq!(
+            pick(&variants, &[], SizeStrategy::Priority),
+            Some("40".into())
+        );
+        assert_eq!(
+            pick(
+                &variants,
+                &sizes(r#"["42", "45-46"]"#),
+                SizeStrategy::Priority
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn most_stock_prefers_the_fullest_preferred_variant() {
+        let variants = variants(&[("40", 5), ("42", 2), ("43", 2), ("44", 9)]);
+
+        assert_eq!(
+            pick(&variants, &sizes(r#"["40-43"]"#), SizeStrategy::MostStock),
+            Some("40".into())
+        );
+        assert_eq!(
+            pick(
+                &variants,
+                &sizes(r#"["43", "42"]"#),
+                SizeStrategy::MostStock
+            ),
+            Some("43".into())
+        );
+    }
+
+    #[test]
+    fn random_is_reproducible_and_stays_preferred() {
+        let variants = variants(&[("40", 5), ("41", 1), ("42", 2), ("43", 2), ("44", 9)]);
+        let preferences = sizes(r#"["41-43"]"#);
+
+        let picks = (0..20)
+            .map(|seed| {
+                let mut rng = SmallRng::seed_from_u64(seed);
+                select(
+                    &variants,
+                    None,
+                    &preferences,
+                    SizeStrategy::Random,
+                    &mut rng,
+                )
+                .unwrap()
+                .size
+                .clone()
+            })
+            .collect::<Vec<_>>();
+
+        assert!(picks
+            .iter()
+            .all(|size| ["41", "42", "43"].contains(&&**size)));
+        assert!(picks.iter().any(|size| size != &picks[0]));
+        assert_eq!(
+            pick(&variants, &preferences, SizeStrategy::Random),
+            pick(&variants, &preferences, SizeStrategy::Random)
+        );
+    }
+
+    #[test]
+    fn parses_scaled_preferences() {
+        assert_eq!(
+            sizes(r#"["US 9", "uk 8-9"]"#),
+            vec![
+                SizePreference::Scaled(Scale::US, Box::new(SizePreference::Exact("9".into()))),
+                SizePreference::Scaled(
+                    Scale::UK,
+                    Box::new(SizePreference::Range(Rank::Number(8.0), Rank::Number(9.0)))
+                ),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&sizes(r#"["us 9", "one size"]"#)).unwrap(),
+            r#"["US 9","one size"]"#
+        );
+        assert!(serde_json::from_str::<Vec<SizePreference>>(r#"["US any"]"#).is_err());
+    }
+
+    #[test]
+    fn converts_scaled_preferences_to_the_product_scale() {
+        let men_shoes = |scale| ProductScale {
+            id: 0,
+            category: Category::MenShoes,
+            scale,
+        };
+        let (men_eu, men_uk) = (&men_shoes(Scale::EU), &men_shoes(Scale::UK));
+        let us_9 = SizePreference::from_str("US 9").unwrap();
+
+        assert!(us_9.matches("42", Some(men_eu)));
+        assert!(!us_9.matches("43", Some(men_eu)));
+        assert!(us_9.matches("8", Some(men_uk)));
+        assert!(!us_9.matches("9", Some(men_uk)));
+        assert!(!us_9.matches("9", None));
+
+        let variants = variants(&[("41", 1), ("42", 0), ("42.5", 3), ("43", 2)]);
+        let mut rng = SmallRng::seed_from_u64(7);
+        let pick = select(
+            &variants,
+            Some(men_eu),
+            &sizes(r#"["US 9", "US 9.5-10"]"#),
+            SizeStrategy::Priority,
+            &mut rng,
+        );
+        assert_eq!(pick.unwrap().size, "42.5");
+    }
+}
@@ -0,0 +1,61 @@
+use crate::country::Country;
+use serde::Deserialize;
+use strum::AsStaticRef;
+use url::Url;
+
+/// A Farfetch Platform Solutions brand site.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Storefront {
+    pub name: String,
+    pub base_url: Url,
+    /// Path prefix for a country, `{locale}` expands to `Country::fps_locale` and
+    /// `{country}` to the lowercase ISO code.
+    #[serde(default = "default_locale_pattern")]
+    pub locale_pattern: String,
+    pub static_name: String,
+    pub folder_name: String,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Card form of the payment gateway. When set, orders are paid through a payment intent
+    /// instead of sending the card to `/finalize`.
+    #[serde(default)]
+    pub payment_gateway: Option<Url>,
+}
+
+fn default_locale_pattern() -> String {
+    "/{locale}".into()
+}
+
+fn default_timeout_ms() -> u64 {
+    10_000
+}
+
+impl Storefront {
+    pub fn builtin(name: &str) -> Option<Storefront> {
+        match name {
+            "emiliopucci" => Some(Storefront {
+                name: "Emilio Pucci".into(),
+                base_url: Url::parse("https://www.emiliopucci.com").ok()?,
+                locale_pattern: default_locale_pattern(),
+                static_name: "emiliopucci".into(),
+                folder_name: "ep-21".into(),
+                timeout_ms: default_timeout_ms(),
+                payment_gateway: None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn country_url(&self, country: &Country) -> Url {
+        let path = self
+            .locale_pattern
+            .replace("{locale}", country.fps_locale())
+            .replace("{country}", &country.as_static().to_lowercase());
+
+        let mut url = self.base_url.clone();
+        url.set_path(&format!("{}/", path.trim_end_matches('/')));
+
+        url
+    }
+}
@@ -1,214 +1,403 @@
+use crate::checkout::{
+    CheckoutPolicy, CheckoutState, FinalizeOutcome, PurchaseCounter, PurchaseOutcome,
+};
+use crate::client::FpsClient;
 use crate::model::FPSAddress;
 use crate::model::FPSCardPaymentIntent;
 use crate::model::FPSCity;
 use crate::model::FPSCreateOrder;
+use crate::model::FPSIntentFinalize;
 use crate::model::FPSItem;
 use crate::model::FPSOrder;
 use crate::model::FPSPatchAddress;
-use crate::model::FPSState;
 use crate::model::Profile;
 use crate::model::Variant;
+use crate::monitor::Release;
+use crate::price::PriceRules;
+use crate::region;
+use crate::scale::ProductScale;
+use crate::size::{self, SizePreference, SizeStrategy};
+use crate::storefront::Storefront;
 use crate::Error;
+use log::debug;
 use log::error;
 use log::info;
+use log::warn;
 use rand::prelude::SmallRng;
-use rand::seq::SliceRandom;
 use rand::SeedableRng;
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, HOST, USER_AGENT};
-use reqwest::Client;
-use reqwest::Url;
-use strum::AsStaticRef;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::Receiver;
+use tokio::sync::watch;
+
+#[derive(Debug, Clone)]
+pub struct TaskOptions {
+    pub policy: CheckoutPolicy,
+    /// Stop once the address is patched instead of submitting payment.
+    pub dry_run: bool,
+    pub max_purchases: u32,
+    /// Declined payments after which the task stops.
+    pub max_declines: u32,
+    /// Shared with the other profiles of the same task.
+    pub purchases: PurchaseCounter,
+    pub size_strategy: SizeStrategy,
+    /// Seeds the random size choice together with the profile alias, from entropy when
+    /// unset.
+    pub seed: Option<u64>,
+    /// Limits the order totals are checked against before paying.
+    pub price: PriceRules,
+    /// Size scales of product `scaleId`s, on top of the known ones.
+    pub scales: Vec<ProductScale>,
+}
+
+impl Default for TaskOptions {
+    fn default() -> TaskOptions {
+        TaskOptions {
+            policy: CheckoutPolicy::default(),
+            dry_run: false,
+            max_purchases: 1,
+            max_declines: 1,
+            purchases: PurchaseCounter::default(),
+            size_strategy: SizeStrategy::default(),
+            seed: None,
+            price: PriceRules::default(),
+            scales: Vec::new(),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Task {
-    client: Client,
+    client: FpsClient,
     profile: Profile,
-    base_url: Url,
-    payment_client: Client,
-    receiver: Receiver<(i64, Vec<Variant>)>,
+    options: TaskOptions,
+    state: (watch::Sender<CheckoutState>, watch::Receiver<CheckoutState>),
+    release: Option<Release>,
+    receiver: Receiver<Release>,
+    purchases: u32,
+    declines: u32,
+    claimed: bool,
+    outcomes: Vec<PurchaseOutcome>,
+    /// Shipping services of the current order.
+    shipping: Option<String>,
+    rng: SmallRng,
 }
 
 impl Task {
-    pub fn new(profile: Profile, receiver: Receiver<(i64, Vec<Variant>)>) -> Result<Task, Error> {
-        let country = &profile.delivery.country;
-        let base_url = Url::parse(country.fps_base_url())?;
-        let mut headers = HeaderMap::new();
-        headers.insert(HOST, HeaderValue::from_static("www.emiliopucci.com:443"));
-        headers.insert(
-            USER_AGENT,
-            HeaderValue::from_static(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0",
-            ),
-        );
-        headers.insert(
-            ACCEPT_LANGUAGE,
-            HeaderValue::from_static(country.accept_language()),
-        );
-        headers.insert("FF-Country", HeaderValue::from_static(country.as_static()));
-        headers.insert(
-            "FF-Currency",
-            HeaderValue::from_static(country.fps_currency()),
-        );
-
-        let client = Client::builder()
-            .use_rustls_tls()
-            .gzip(true)
-            .default_headers(headers)
-            .cookie_store(true)
-            .user_agent("Mozilla/5.0 (iPhone; CPU iPhone OS 14_4_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1")
-            .build()?;
-
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            HOST,
-            HeaderValue::from_static("fps-farfetch-payment-gateway.farfetch.net"),
-        );
-        headers.insert(
-            USER_AGENT,
-            HeaderValue::from_static(
-                "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:89.0) Gecko/20100101 Firefox/89.0",
-            ),
-        );
-        headers.insert("True-Client-IP", HeaderValue::from_static("127.0.0.1"));
-
-        let payment_client = Client::builder()
-            .use_rustls_tls()
-            .gzip(true)
-            .cookie_store(true)
-            .default_headers(headers)
-            .user_agent("Mozilla/5.0 (iPhone; CPU iPhone OS 14_4_2 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/14.0 Mobile/15E148 Safari/604.1")
-            .build()?;
+    pub fn new(
+        profile: Profile,
+        storefront: Storefront,
+        options: TaskOptions,
+        receiver: Receiver<Release>,
+    ) -> Result<Task, Error> {
+        let client = FpsClient::new(storefront, profile.delivery.country.clone())?;
+        let rng = match options.seed {
+            Some(seed) => SmallRng::seed_from_u64(profile_seed(seed, &profile.alias)),
+            None => SmallRng::from_entropy(),
+        };
 
         Ok(Task {
             client,
             profile,
+            options,
+            state: watch::channel(CheckoutState::Started),
+            release: None,
             receiver,
-            base_url,
-            payment_client,
+            purchases: 0,
+            declines: 0,
+            claimed: false,
+            outcomes: Vec::new(),
+            shipping: None,
+            rng,
         })
     }
 
-    pub async fn start(&mut self) -> Result<(), Error> {
-        for retry in 0..=5 {
-            match self.create_session().await {
-                Ok(_) => break,
-                Err(why) => match retry {
-                    5 => return Err(why),
-                    _ => continue,
-                },
+    pub fn state(&self) -> watch::Receiver<CheckoutState> {
+        self.state.1.clone()
+    }
+
+    /// Starts the task and resumes it from its last state after transient errors, as often
+    /// as the resume policy allows.
+    pub async fn run(&mut self) -> Result<Vec<PurchaseOutcome>, Error> {
+        let policy = self.options.policy.resume;
+        let mut attempt = 1;
+
+        loop {
+            match self.start().await {
+                Err(why) if why.is_transient() && attempt < policy.attempts => {
+                    attempt += 1;
+                    warn!(
+                        "profile={} attempt={} state={} message=\"resuming checkout\"",
+                        &self.profile.alias,
+                        attempt,
+                        *self.state.1.borrow()
+                    );
+                    tokio::time::sleep(Duration::from_millis(policy.delay_ms)).await;
+                }
+                result => return result,
             }
         }
+    }
+
+    /// Drives the checkout until the task is done and returns every payment outcome. On a
+    /// transient error the last successful state is kept, so calling `start` again resumes
+    /// the same order.
+    pub async fn start(&mut self) -> Result<Vec<PurchaseOutcome>, Error> {
+        loop {
+            let state = self.state.1.borrow().clone();
+            let next = match state {
+                CheckoutState::Started => self
+                    .options
+                    .policy
+                    .session
+                    .run(|| self.create_session())
+                    .await
+                    .map(|_| CheckoutState::SessionCreated),
+                CheckoutState::SessionCreated if !self.has_capacity() => Ok(CheckoutState::Done {
+                    purchases: self.purchases,
+                }),
+                CheckoutState::SessionCreated => {
+                    let (product, variant) = self.wait_for_size().await?;
+
+                    self.options
+                        .policy
+                        .order
+                        .run(|| self.create_order(product, &variant))
+                        .await
+                        .map(|order| CheckoutState::OrderCreated { order })
+                }
+                CheckoutState::OrderCreated { order } => {
+                    let policy = self.options.policy.address;
 
-        info!("email={} message=\"created session\"", &self.profile.email);
+                    match policy.run(|| self.patch_address(order.id)).await {
+                        Ok(order) => policy.run(|| self.set_shipping(&order)).await,
+                        Err(why) => Err(why),
+                    }
+                    .map(|(order, shipping)| {
+                        self.shipping = shipping;
+                        order
+                    })
+                    .and_then(|order| self.check_totals(order))
+                    .map(|order| CheckoutState::AddressPatched { order })
+                }
+                CheckoutState::AddressPatched { order } if self.options.dry_run => {
+                    let totals = &order.checkout_order;
+                    info!(
+                        "profile={} order={} currency={} subtotal={} shipping={} taxes={} total={} message=\"dry run, skipping payment\"",
+                        &self.profile.alias,
+                        order.id,
+                        totals.currency,
+                        totals.sub_total_amount,
+                        totals.total_shipping_fee,
+                        totals.total_taxes,
+                        totals.grand_total
+                    );
 
-        let mut rng = SmallRng::from_entropy();
+                    self.transition(CheckoutState::DryRunCompleted { order: order.id });
+                    return Ok(self.outcomes.clone());
+                }
+                CheckoutState::AddressPatched { .. }
+                    if !self.claimed && !self.options.purchases.claim() =>
+                {
+                    Ok(CheckoutState::Done {
+                        purchases: self.purchases,
+                    })
+                }
+                CheckoutState::AddressPatched { order } => match self.payment_method(&order) {
+                    Ok(payment_method) => {
+                        self.claimed = true;
+                        // From here on the card may have been charged, so a failed payment is
+                        // confirmed from the order and never sent again.
+                        self.transition(CheckoutState::PaymentSubmitted { order: order.id });
 
-        let (product, variants) = self.receiver.recv().await?;
+                        // Only a payment the storefront declined gives the claim back. Any other
+                        // failure may still have charged the card, so the task stops holding it.
+                        self.submit_payment(&order, &payment_method)
+                            .await
+                            .map(|outcome| self.settle(order.id, outcome))
+                    }
+                    Err(why) => {
+                        self.options.purchases.release();
+                        self.claimed = false;
 
-        loop {
-            let mut order_id = 0;
-            // let mut payment_intent: String = "".into();
-
-            for i in 0..=10 {
-                if let Some(variant) = variants.choose(&mut rng).cloned() {
-                    // dbg!(&variant);
-                    match self.create_order(product, variant).await {
-                        Ok(order) => {
-                            order_id = order.id;
-                            // payment_intent = order.checkout_order.payment_intent_id;
-
-                            info!("order={} message=\"created order\"", order_id);
-
-                            break;
-                        }
-                        Err(why) => match i {
-                            10 => return Err(why),
-                            _ => continue,
-                        },
+                        Err(why)
                     }
-                } else {
+                },
+                CheckoutState::PaymentSubmitted { order } => self
+                    .options
+                    .policy
+                    .confirmation
+                    .run(|| self.confirm_payment(order))
+                    .await
+                    .map(|outcome| self.settle(order, outcome)),
+                CheckoutState::Declined { .. } if self.declines >= self.options.max_declines => {
+                    Ok(CheckoutState::Done {
+                        purchases: self.purchases,
+                    })
+                }
+                CheckoutState::Confirmed { .. } | CheckoutState::Declined { .. } => {
+                    // Buy the next unit from a fresh monitor update, not the stale variants.
+                    self.release = None;
+                    Ok(CheckoutState::SessionCreated)
+                }
+                CheckoutState::DryRunCompleted { .. }
+                | CheckoutState::ActionRequired { .. }
+                | CheckoutState::Done { .. } => return Ok(self.outcomes.clone()),
+                CheckoutState::Failed { reason } => return Err(Error::Unknown(reason)),
+            };
+
+            match next {
+                Ok(next) => self.transition(next),
+                Err(why) if why.is_transient() => {
                     error!(
-                        "email={} message=\"failed to get variant\"",
-                        &self.profile.email
+                        "profile={} state={} error=\"{}\" message=\"checkout interrupted\"",
+                        &self.profile.alias,
+                        *self.state.1.borrow(),
+                        why
                     );
-                    return Err(Error::Unknown("missing variant".into()));
+                    return Err(why);
+                }
+                Err(why) => {
+                    self.transition(CheckoutState::Failed {
+                        reason: why.to_string(),
+                    });
+                    return Err(why);
                 }
             }
+        }
+    }
 
-            for i in 0..=10 {
-                match self.patch_address(order_id).await {
-                    Ok(_) => {
-                        info!("order={} message=\"patched address\"", order_id);
-                        break;
-                    }
-                    Err(why) => match i {
-                        10 => return Err(why),
-                        _ => continue,
-                    },
-                }
+    /// Records a payment outcome, keeping the claimed unit unless the payment was declined.
+    fn settle(&mut self, order: i64, outcome: FinalizeOutcome) -> CheckoutState {
+        info!(
+            "profile={} order={} outcome={}",
+            &self.profile.alias, order, &outcome
+        );
+        self.outcomes.push(PurchaseOutcome {
+            outcome: outcome.clone(),
+            shipping: self.shipping.clone(),
+        });
+
+        match outcome {
+            FinalizeOutcome::Approved { reference } => {
+                self.purchases += 1;
+                self.claimed = false;
+
+                CheckoutState::Confirmed { order, reference }
             }
+            FinalizeOutcome::Declined { reason } => {
+                self.declines += 1;
+                self.options.purchases.release();
+                self.claimed = false;
 
-            match self.submit_payment(order_id).await {
-                Ok(_) => {
-                    info!("order={} message=\"submitted order\"", order_id);
-                }
-                Err(_) => {
-                    error!("order={} message=\"failed to submit order\"", order_id);
+                CheckoutState::Declined { order, reason }
+            }
+            FinalizeOutcome::RequiresAction { url } => CheckoutState::ActionRequired { order, url },
+            FinalizeOutcome::Pending { .. } => CheckoutState::PaymentSubmitted { order },
+        }
+    }
+
+    fn has_capacity(&self) -> bool {
+        self.purchases < self.options.max_purchases && self.options.purchases.has_capacity()
+    }
+
+    fn transition(&mut self, next: CheckoutState) {
+        info!(
+            "storefront={} profile={} state={}",
+            &self.client.storefront().name,
+            &self.profile.alias,
+            &next
+        );
+
+        // The task holds a receiver itself, so sending cannot fail.
+        let _ = self.state.0.send(next);
+    }
+
+    async fn wait_for_release(&mut self) -> Result<Release, Error> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(release) => {
+                    self.release = Some(release.clone());
+                    return Ok(release);
                 }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(why) => return Err(why.into()),
             }
         }
+    }
+
+    /// Picks a variant the profile wears from the current release, waiting for restocks
+    /// until one of its sizes is in stock.
+    async fn wait_for_size(&mut self) -> Result<(i64, Variant), Error> {
+        loop {
+            let release = match self.release.clone() {
+                Some(release) => release,
+                None => self.wait_for_release().await?,
+            };
+
+            let scale = ProductScale::of(release.scale_id, &self.options.scales);
+            let strategy = self.options.size_strategy;
+            if let Some(variant) = size::select(
+                &release.variants,
+                scale,
+                &self.profile.sizes,
+                strategy,
+                &mut self.rng,
+            ) {
+                return Ok((release.product, variant.clone()));
+            }
+
+            let scaled = self
+                .profile
+                .sizes
+                .iter()
+                .any(|size| matches!(size, SizePreference::Scaled(..)));
+            if scale.is_none() && scaled {
+                warn!(
+                    "profile={} product={} scale={} message=\"unknown size scale, sizes with a scale cannot match\"",
+                    &self.profile.alias, release.product, release.scale_id
+                );
+            }
 
-        // Ok(())
+            info!(
+                "profile={} sizes={} message=\"no preferred size in stock, waiting for restock\"",
+                &self.profile.alias,
+                self.profile
+                    .sizes
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+            self.release = None;
+        }
     }
 
     async fn create_session(&self) -> Result<(), Error> {
-        let url = self.base_url.join("/api/users/me")?;
-        self.client.get(url).send().await?.error_for_status()?;
+        self.client.me().await?;
 
         Ok(())
     }
 
-    async fn create_order(&self, product: i64, variant: Variant) -> Result<FPSOrder, Error> {
-        let url = {
-            let mut url = self.base_url.clone();
-            url.set_path("/api/checkout/v1/orders");
-            url
-        };
-
+    async fn create_order(&self, product: i64, variant: &Variant) -> Result<FPSOrder, Error> {
         let body = FPSCreateOrder {
             guest_user_email: &self.profile.email,
-            use_payment_intent: false,
+            use_payment_intent: self.client.storefront().payment_gateway.is_some(),
+            // The mode the storefront checkout creates orders in. The order then offers
+            // shipping options per group of merchants and one is chosen for each.
             shipping_mode: "byMerchant",
             items: vec![FPSItem {
                 merchant_id: variant.merchant_id,
-                variant_id: variant.id,
+                variant_id: variant.id.clone(),
                 product_id: product,
                 quantity: 1,
             }],
         };
 
-        let response = self
-            .client
-            .post(url)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        let body = response.bytes().await?;
-        let order: FPSOrder = serde_json::from_slice(&body)?;
-
-        Ok(order)
+        self.client.create_order(&body).await
     }
 
-    async fn patch_address(&self, order: i64) -> Result<(), Error> {
-        let url = {
-            let mut url = self.base_url.clone();
-            url.set_path("/api/checkout/v1/orders/");
-            url.join(&order.to_string())?
-        };
-
+    async fn patch_address(&self, order: i64) -> Result<FPSOrder, Error> {
         let billing_address = FPSAddress {
             first_name: &self.profile.billing.first_name,
             last_name: &self.profile.billing.last_name,
@@ -219,9 +408,7 @@ impl Task {
             city: FPSCity {
                 name: &self.profile.billing.city,
             },
-            state: FPSState {
-                name: &self.profile.billing.state.clone().unwrap_or("".into()),
-            },
+            state: region::fps_state(&self.profile.billing),
             zip_code: &self.profile.billing.zip,
             phone: &self.profile.phone,
         };
@@ -236,9 +423,7 @@ impl Task {
             city: FPSCity {
                 name: &self.profile.delivery.city,
             },
-            state: FPSState {
-                name: &self.profile.delivery.state.clone().unwrap_or("".into()),
-            },
+            state: region::fps_state(&self.profile.delivery),
             zip_code: &self.profile.delivery.zip,
             phone: &self.profile.phone,
         };
@@ -248,123 +433,92 @@ impl Task {
             shipping_address,
         };
 
-        let response = self
-            .client
-            .patch(url)
-            .json(&body)
-            .send()
-            .await?
-            .error_for_status()?;
+        self.client.patch_order_address(order, &body).await
+    }
 
-        let body = response.bytes().await?;
-        let _order: FPSOrder = serde_json::from_slice(&body)?;
+    /// Sends the profile's choice among the order's shipping options for each group of
+    /// merchants, if it offers any, and returns the updated order with the chosen services.
+    async fn set_shipping(&self, order: &FPSOrder) -> Result<(FPSOrder, Option<String>), Error> {
+        if order.shipping_options.is_empty() {
+            return Ok((order.clone(), None));
+        }
 
-        Ok(())
+        let options = self
+            .profile
+            .shipping
+            .select_each(&order.shipping_options)
+            .map_err(Error::Shipping)?;
+        let updated = self.client.set_order_shipping(order.id, &options).await?;
+
+        let mut services = Vec::new();
+        for option in options {
+            info!(
+                "profile={} order={} merchants={:?} shipping=\"{}\" price={} message=\"selected shipping option\"",
+                &self.profile.alias,
+                order.id,
+                &option.merchants,
+                &option.shipping_service.name,
+                option.formatted_price
+            );
+            if !services.contains(&option.shipping_service.name) {
+                services.push(option.shipping_service.name.clone());
+            }
+        }
+
+        Ok((updated, Some(services.join(", "))))
     }
 
-    // async fn fetch_payment_form(&self, payment_intent: &str) -> Result<(String, String), Error> {
-    //     let url = Url::parse_with_params(
-    //         "https://org-pay-br15k8.farfetch.net",
-    //         &[
-    //             ("paymentIntentId", payment_intent),
-    //             ("staticName", "emiliopucci"),
-    //             ("folderName", "ep-21"),
-    //             ("locale", "en-US"),
-    //         ],
-    //     )?;
-
-    //     let response = self
-    //         .payment_client
-    //         .get(url)
-    //         .send()
-    //         .await?
-    //         .error_for_status()?;
-
-    //     let text = response.text().await?;
-    //     let split = text.split("var sessionId = '").collect::<Vec<_>>();
-    //     let second_split = split[1].split("';").collect::<Vec<_>>();
-
-    //     let third_split = text.split("name=\"_csrf\" value=\"").collect::<Vec<_>>();
-    //     let fourth_split = third_split[1].split("\"").collect::<Vec<_>>();
-
-    //     let session = second_split[0].to_string();
-    //     let csrf = fourth_split[0].to_string();
-
-    //     Ok((session, csrf))
-    // }
-
-    // async fn create_payment_intent(&self, session: &str, csrf: &str) -> Result<String, Error> {
-    //     let url = Url::parse_with_params(
-    //         "https://org-pay-br15k8.farfetch.net/instruments",
-    //         &[("sessionId", session)],
-    //     )?;
-
-    //     let holder_name = format!(
-    //         "{} {}",
-    //         &self.profile.billing.first_name, &self.profile.billing.last_name
-    //     );
-
-    //     let intent = FPSCardPaymentIntent {
-    //         card_number: &self.profile.card.number,
-    //         card_holder_name: &holder_name,
-    //         card_expiry_month: &self.profile.card.expiry_month,
-    //         card_expiry_year: &self.profile.card.expiry_year,
-    //         card_cvv: &self.profile.card.cvv,
-    //     };
-
-    //     // "paymentMethodType": "CreditCard",
-    //                                         // "paymentMethodId": "e13bb06b-392b-49a0-8acd-3f44416e3234",
-    //                                         // "savePaymentMethodAsToken": True
-
-    //     let response = self
-    //         .payment_client
-    //         .post(url)
-    //         .json(&intent)
-    //         .header("X-CSRF-TOKEN", csrf)
-    //         .send()
-    //         .await?
-    //         .error_for_status()?;
-
-    //     let body = response.bytes().await?;
-    //     let created_intent: FPSCreatedIntent = serde_json::from_slice(&body)?;
-
-    //     dbg!(&created_intent);
-
-    //     Ok(created_intent.created_at)
-    // }
-
-    // async fn append_payment_intent(&self, order: i64, intent: &str) -> Result<(), Error> {
-    //     let url = {
-    //         let mut url = self.base_url.clone();
-    //         let path = format!("/api{}", intent);
-    //         url.set_path(&path);
-
-    //         url
-    //     };
-
-    // let response = self
-    //     .payment_client
-    //     .post(url)
-    //     .send()
-    //     .await?
-    //     .error_for_status()?;
-
-    //     dbg!(&response);
-    //     dbg!(response.text().await);
-    //     panic!();
-
-    //     Ok(())
-    // }
-
-    async fn submit_payment(&self, order: i64) -> Result<(), Error> {
-        let url = {
-            let mut url = self.base_url.clone();
-            let path = format!("/api/checkout/v1/orders/{}/finalize", order);
-            url.set_path(&path);
-
-            url
-        };
+    /// Aborts the checkout when the order is not in the delivery country's currency, costs
+    /// more than the product was released at or breaks the task's limits on the totals.
+    fn check_totals(&self, order: FPSOrder) -> Result<FPSOrder, Error> {
+        let price = self
+            .release
+            .as_ref()
+            .and_then(|release| release.price.as_ref());
+        let problems = self.options.price.check_order(
+            &order.checkout_order,
+            price,
+            self.profile.delivery.country.fps_currency(),
+        );
 
+        if problems.is_empty() {
+            Ok(order)
+        } else {
+            Err(Error::OrderTotals(problems.join("; ")))
+        }
+    }
+
+    /// Picks the payment method the order offers for the brand of the profile's card.
+    fn payment_method(&self, order: &FPSOrder) -> Result<String, Error> {
+        let brand = self
+            .profile
+            .card
+            .brand()
+            .ok_or_else(|| Error::PaymentMethod("unrecognised card number".into()))?;
+
+        order
+            .payment_methods
+            .credit_card
+            .credit_cards
+            .iter()
+            .find(|card| brand.matches(card))
+            .map(|card| card.id.clone())
+            .ok_or_else(|| {
+                Error::PaymentMethod(format!(
+                    "{} is not accepted in {}",
+                    brand,
+                    self.profile.delivery.country.fps_country().name
+                ))
+            })
+    }
+
+    /// Pays `order` by sending the card to `/finalize`, or, for storefronts with a payment
+    /// gateway, by registering the card with the order's payment intent first.
+    async fn submit_payment(
+        &self,
+        order: &FPSOrder,
+        payment_method: &str,
+    ) -> Result<FinalizeOutcome, Error> {
         let holder_name = format!(
             "{} {}",
             &self.profile.billing.first_name, &self.profile.billing.last_name
@@ -377,24 +531,904 @@ impl Task {
             card_expiry_year: self.profile.card.expiry_year,
             card_cvv: &self.profile.card.cvv,
             payment_method_type: "CreditCard",
-            payment_method_id: "e13bb06b-392b-49a0-8acd-3f44416e3234",
+            payment_method_id: payment_method,
             save_payment_method_as_token: true,
         };
 
-        let response = self
-            .client
-            .post(url)
-            .json(&card)
-            .send()
-            .await?
-            .error_for_status()?;
+        let response = match self.client.storefront().payment_gateway {
+            Some(_) => {
+                let intent = order
+                    .checkout_order
+                    .payment_intent_id
+                    .as_deref()
+                    .ok_or_else(|| Error::PaymentForm("order has no payment intent".into()))?;
 
-        dbg!(&response);
+                let form = self.client.fetch_payment_form(intent).await?;
+                let instrument = self.client.create_payment_instrument(&form, &card).await?;
+                info!(
+                    "order={} instrument={} message=\"created payment instrument\"",
+                    order.id, &instrument.id
+                );
 
-        if let Ok(text) = response.text().await {
-            dbg!(text);
+                let body = FPSIntentFinalize {
+                    payment_intent_id: intent,
+                    instrument_id: &instrument.id,
+                };
+                self.client.finalize_order_intent(order.id, &body).await?
+            }
+            None => self.client.finalize_order(order.id, &card).await?,
+        };
+        debug!("order={} response={:?}", order.id, response);
+
+        Ok(response.into())
+    }
+
+    /// Reads the outcome of a pending payment from its order, failing transiently while the
+    /// order is still open so the confirmation policy polls again.
+    async fn confirm_payment(&self, order: i64) -> Result<FinalizeOutcome, Error> {
+        match self.client.get_order(order).await?.into() {
+            FinalizeOutcome::Pending { .. } => Err(Error::PaymentPending(order)),
+            outcome => Ok(outcome),
         }
+    }
+}
 
-        Ok(())
+/// Mixes the alias into a task seed with FNV-1a, so the profiles of a seeded task pick
+/// different random sizes and each still picks the same ones on every run.
+fn profile_seed(seed: u64, alias: &str) -> u64 {
+    alias
+        .bytes()
+        .fold(seed ^ 0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checkout::PurchaseCounter;
+    use crate::country::Country;
+    use crate::mock::{self, MockServer, Scenario};
+    use crate::monitor::Monitor;
+    use crate::price::PriceRules;
+    use crate::shipping::ShippingPreference;
+    use reqwest::Method;
+    use serde_json::json;
+    use tokio::sync::broadcast;
+    use tokio::task::JoinHandle;
+
+    struct Run {
+        server: MockServer,
+        task: JoinHandle<Result<Vec<PurchaseOutcome>, Error>>,
+        monitor: JoinHandle<Result<(), Error>>,
+        state: watch::Receiver<CheckoutState>,
+    }
+
+    impl Drop for Run {
+        fn drop(&mut self) {
+            self.task.abort();
+            self.monitor.abort();
+        }
+    }
+
+    fn finalized(purchases: &[PurchaseOutcome]) -> Vec<FinalizeOutcome> {
+        purchases
+            .iter()
+            .map(|purchase| purchase.outcome.clone())
+            .collect()
+    }
+
+    /// A task for `profile` on `storefront` with the product already released in the
+    /// profile's delivery country. The sender and release are returned for later updates.
+    async fn released(
+        storefront: &Storefront,
+        profile: Profile,
+        options: TaskOptions,
+    ) -> (Task, broadcast::Sender<Release>, Release) {
+        let (sender, receiver) = broadcast::channel(32);
+        let client = FpsClient::new(storefront.clone(), profile.delivery.country.clone()).unwrap();
+        let task = Task::new(profile, storefront.clone(), options, receiver).unwrap();
+
+        let release = Release::from(client.get_product(mock::PRODUCT_ID).await.unwrap());
+        sender.send(release.clone()).unwrap();
+
+        (task, sender, release)
+    }
+
+    async fn run(scenario: &str) -> Run {
+        run_with(scenario, TaskOptions::default()).await
+    }
+
+    async fn run_with(scenario: &str, options: TaskOptions) -> Run {
+        let server = MockServer::with_scenario(Scenario::load(scenario)).await;
+        let storefront = server.storefront();
+        let (sender, _) = broadcast::channel(32);
+
+        let mut task = Task::new(
+            mock::profile(Country::GB),
+            storefront.clone(),
+            options,
+            sender.subscribe(),
+        )
+        .unwrap();
+        let mut monitor = Monitor::new(
+            mock::PRODUCT_ID.into(),
+            &storefront,
+            &Country::GB,
+            Duration::from_millis(20),
+            PriceRules::default(),
+            sender,
+        )
+        .unwrap();
+
+        Run {
+            server,
+            state: task.state(),
+            task: tokio::spawn(async move { task.start().await }),
+            monitor: tokio::spawn(async move { monitor.start().await }),
+        }
+    }
+
+    #[tokio::test]
+    async fn monitor_release_reaches_finalize() {
+        let run = run("happy_path").await;
+
+        let finalize = run
+            .server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+        assert_eq!(finalize.body["cardNumber"], "4111111111111111");
+
+        let order = run
+            .server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(
+            order.body["items"][0]["variantId"],
+            "b3f4a2de-0c3e-4f1a-9a53-7d0e3b0a1c01"
+        );
+
+        let address = run
+            .server
+            .wait_for(Method::PATCH, "checkout/v1/orders/1")
+            .await
+            .unwrap();
+        assert_eq!(address.body["shippingAddress"]["country"]["id"], 215);
+    }
+
+    #[tokio::test]
+    async fn waits_for_restock() {
+        let run = run("restock").await;
+
+        run.server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+
+        let requests = run.server.requests();
+        let first_order = requests
+            .iter()
+            .position(|request| request.path == "checkout/v1/orders")
+            .unwrap();
+        let polls = requests[..first_order]
+            .iter()
+            .filter(|request| request.path == "products/16472289")
+            .count();
+        assert!(polls > 3, "ordered after {} polls", polls);
+    }
+
+    #[tokio::test]
+    async fn monitor_keeps_polling_through_errors() {
+        let run = run("product_errors").await;
+
+        run.server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+
+        assert!(run.server.count(Method::GET, "products/16472289") >= 3);
+    }
+
+    #[tokio::test]
+    async fn retries_create_order_after_server_errors() {
+        let run = run("create_order_5xx").await;
+
+        run.server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+
+        assert!(run.server.count(Method::POST, "checkout/v1/orders") >= 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_create_order_keeps_failing() {
+        let mut run = run("create_order_down").await;
+
+        let result = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept retrying")
+            .unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(run.server.count(Method::POST, "checkout/v1/orders"), 11);
+    }
+
+    #[tokio::test]
+    async fn retries_address_after_timeout() {
+        let run = run("address_timeout").await;
+
+        run.server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+
+        assert_eq!(run.server.count(Method::PATCH, "checkout/v1/orders/1"), 2);
+    }
+
+    #[tokio::test]
+    async fn stops_after_declined_payment() {
+        let mut run = run("finalize_declined").await;
+
+        let outcomes = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept buying")
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(
+            finalized(&outcomes).as_slice(),
+            [FinalizeOutcome::Declined { .. }]
+        ));
+        assert_eq!(*run.state.borrow(), CheckoutState::Done { purchases: 0 });
+        assert_eq!(run.server.count(Method::POST, "checkout/v1/orders"), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_declined_payments_up_to_the_limit() {
+        let options = TaskOptions {
+            max_declines: 3,
+            ..TaskOptions::default()
+        };
+        let mut run = run_with("finalize_declined", options).await;
+
+        let outcomes = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept buying")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 3);
+        assert_eq!(*run.state.borrow(), CheckoutState::Done { purchases: 0 });
+        assert_eq!(run.server.count(Method::POST, "checkout/v1/orders"), 3);
+        assert_eq!(
+            run.server
+                .count(Method::POST, "checkout/v1/orders/3/finalize"),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn run_resumes_after_transient_failure() {
+        let server = MockServer::with_scenario(Scenario::load("address_down")).await;
+        let mut options = TaskOptions::default();
+        options.policy.resume.delay_ms = 0;
+        let profile = mock::profile(Country::GB);
+        let (mut task, _sender, _) = released(&server.storefront(), profile, options).await;
+
+        let outcomes = task.run().await.unwrap();
+
+        assert_eq!(
+            finalized(&outcomes),
+            vec![FinalizeOutcome::Approved {
+                reference: "3XK9QF".into()
+            }]
+        );
+        assert_eq!(server.count(Method::POST, "checkout/v1/orders"), 1);
+    }
+
+    #[tokio::test]
+    async fn seeds_profiles_of_a_task_apart() {
+        let server = MockServer::with_scenario(Scenario::load("numeric_sizes")).await;
+        let options = TaskOptions {
+            size_strategy: SizeStrategy::Random,
+            seed: Some(7),
+            ..TaskOptions::default()
+        };
+
+        let mut picks = Vec::new();
+        for alias in &["first", "second", "first"] {
+            let mut profile = mock::profile(Country::GB);
+            profile.alias = alias.to_string();
+            let (mut task, _sender, _) =
+                released(&server.storefront(), profile, options.clone()).await;
+
+            let mut sizes = Vec::new();
+            for _ in 0..8 {
+                sizes.push(task.wait_for_size().await.unwrap().1.id);
+            }
+            picks.push(sizes);
+        }
+
+        assert_ne!(picks[0], picks[1]);
+        assert_eq!(picks[0], picks[2]);
+    }
+
+    #[tokio::test]
+    async fn never_resends_a_failed_payment() {
+        let server = MockServer::with_scenario(Scenario::load("finalize_5xx")).await;
+        let mut options = TaskOptions::default();
+        options.policy.resume.delay_ms = 0;
+        let profile = mock::profile(Country::GB);
+        let (mut task, _sender, _) = released(&server.storefront(), profile, options).await;
+        let state = task.state();
+
+        assert!(task.run().await.unwrap_err().is_transient());
+
+        assert_eq!(
+            *state.borrow(),
+            CheckoutState::PaymentSubmitted { order: 1 }
+        );
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            1
+        );
+        assert_eq!(server.count(Method::GET, "checkout/v1/orders/1"), 6);
+    }
+
+    #[tokio::test]
+    async fn resumes_order_after_transient_failure() {
+        let server = MockServer::with_scenario(Scenario::load("address_down")).await;
+        let profile = mock::profile(Country::GB);
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+        let state = task.state();
+
+        assert!(task.start().await.unwrap_err().is_transient());
+        assert!(matches!(
+            &*state.borrow(),
+            CheckoutState::OrderCreated { order } if order.id == 1
+        ));
+
+        let resumed = tokio::spawn(async move { task.start().await });
+        server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized the resumed order");
+        resumed.abort();
+
+        let requests = server.requests();
+        let finalize = requests
+            .iter()
+            .position(|request| request.path == "checkout/v1/orders/1/finalize")
+            .unwrap();
+        let orders = requests[..finalize]
+            .iter()
+            .filter(|request| request.path == "checkout/v1/orders")
+            .count();
+        assert_eq!(orders, 1);
+    }
+
+    #[tokio::test]
+    async fn dry_run_stops_before_finalize() {
+        let server = MockServer::start().await;
+        let options = TaskOptions {
+            dry_run: true,
+            ..TaskOptions::default()
+        };
+        let (mut task, _sender, _) =
+            released(&server.storefront(), mock::profile(Country::GB), options).await;
+        let state = task.state();
+
+        task.start().await.unwrap();
+
+        assert_eq!(*state.borrow(), CheckoutState::DryRunCompleted { order: 1 });
+        assert_eq!(server.count(Method::PATCH, "checkout/v1/orders/1"), 1);
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_after_profile_limit() {
+        let mut run = run("happy_path").await;
+
+        let outcomes = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept buying")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            finalized(&outcomes),
+            vec![FinalizeOutcome::Approved {
+                reference: "3XK9QF".into()
+            }]
+        );
+        assert_eq!(run.server.count(Method::POST, "checkout/v1/orders"), 1);
+        assert_eq!(
+            run.server
+                .count(Method::POST, "checkout/v1/orders/1/finalize"),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn total_limit_is_shared_across_profiles() {
+        let server = MockServer::start().await;
+        let options = TaskOptions {
+            max_purchases: 5,
+            purchases: PurchaseCounter::new(Some(1)),
+            ..TaskOptions::default()
+        };
+
+        let profile = mock::profile(Country::GB);
+        let (mut first, _first_sender, _) =
+            released(&server.storefront(), profile.clone(), options.clone()).await;
+        let (mut second, _second_sender, _) =
+            released(&server.storefront(), profile, options).await;
+        let states = [first.state(), second.state()];
+
+        let (first, second) = futures::join!(first.start(), second.start());
+        first.unwrap();
+        second.unwrap();
+
+        let finalized = server
+            .requests()
+            .iter()
+            .filter(|request| request.path.ends_with("/finalize"))
+            .count();
+        assert_eq!(finalized, 1);
+
+        let mut purchases = states
+            .iter()
+            .map(|state| match *state.borrow() {
+                CheckoutState::Done { purchases } => purchases,
+                ref state => panic!("unexpected state {}", state),
+            })
+            .collect::<Vec<_>>();
+        purchases.sort_unstable();
+        assert_eq!(purchases, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn stops_when_payment_requires_action() {
+        let mut run = run("finalize_3ds").await;
+
+        let outcomes = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept buying")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            finalized(&outcomes),
+            vec![FinalizeOutcome::RequiresAction {
+                url: "https://3ds.example.com/acs".into()
+            }]
+        );
+        assert_eq!(run.server.count(Method::POST, "checkout/v1/orders"), 1);
+    }
+
+    #[tokio::test]
+    async fn confirms_pending_payment_from_order() {
+        let mut run = run("finalize_pending").await;
+
+        let outcomes = tokio::time::timeout(Duration::from_secs(5), &mut run.task)
+            .await
+            .expect("task kept buying")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            finalized(&outcomes),
+            vec![
+                FinalizeOutcome::Pending {
+                    reference: Some("3XK9QF".into())
+                },
+                FinalizeOutcome::Approved {
+                    reference: "3XK9QF".into()
+                }
+            ]
+        );
+        assert_eq!(run.server.count(Method::GET, "checkout/v1/orders/1"), 3);
+    }
+
+    #[tokio::test]
+    async fn keeps_claim_when_payment_response_is_unreadable() {
+        let server = MockServer::with_scenario(Scenario::load("finalize_empty")).await;
+        let options = TaskOptions {
+            purchases: PurchaseCounter::new(Some(1)),
+            ..TaskOptions::default()
+        };
+        let purchases = options.purchases.clone();
+        let profile = mock::profile(Country::GB);
+        let (mut task, _sender, _) = released(&server.storefront(), profile, options).await;
+        let state = task.state();
+
+        let why = tokio::time::timeout(Duration::from_secs(5), task.start())
+            .await
+            .expect("task kept buying")
+            .unwrap_err();
+
+        assert!(matches!(why, Error::SerdeJSON(_)));
+        assert!(matches!(&*state.borrow(), CheckoutState::Failed { .. }));
+        assert!(!purchases.has_capacity());
+        assert_eq!(server.count(Method::POST, "checkout/v1/orders"), 1);
+    }
+
+    #[tokio::test]
+    async fn declines_pending_payment_when_order_is_cancelled() {
+        let server = MockServer::with_scenario(Scenario::load("finalize_pending_cancelled")).await;
+        let options = TaskOptions {
+            purchases: PurchaseCounter::new(Some(1)),
+            ..TaskOptions::default()
+        };
+        let purchases = options.purchases.clone();
+        let profile = mock::profile(Country::GB);
+        let (mut task, _sender, _) = released(&server.storefront(), profile, options).await;
+
+        let running = tokio::spawn(async move { task.start().await });
+        server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .expect("task never finalized an order");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !purchases.has_capacity() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("declined payment kept its claim");
+        running.abort();
+
+        assert_eq!(server.count(Method::GET, "checkout/v1/orders/1"), 2);
+        assert_eq!(server.count(Method::POST, "checkout/v1/orders"), 1);
+    }
+
+    #[tokio::test]
+    async fn pays_with_method_offered_for_card_brand() {
+        let mut profile = mock::profile(Country::GB);
+        profile.card.number = "5555555555554444".into();
+        let server = MockServer::start().await;
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        task.start().await.unwrap();
+
+        let finalize = server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .unwrap();
+        assert_eq!(
+            finalize.body["paymentMethodId"],
+            "0f2c4a7e-5b1d-4c8e-9f3a-2d6b8e1c7a45"
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_when_card_brand_is_not_offered() {
+        let mut profile = mock::profile(Country::GB);
+        profile.card.number = "6011111111111117".into();
+        let server = MockServer::start().await;
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+        let state = task.state();
+
+        let why = task.start().await.unwrap_err();
+
+        assert!(matches!(why, Error::PaymentMethod(_)));
+        assert_eq!(
+            why.to_string(),
+            "payment_method=Discover is not accepted in United Kingdom"
+        );
+        assert!(matches!(*state.borrow(), CheckoutState::Failed { .. }));
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn pays_through_payment_gateway() {
+        let server = MockServer::start().await;
+        let storefront = server.gateway_storefront();
+        let (mut task, _sender, _) = released(
+            &storefront,
+            mock::profile(Country::GB),
+            TaskOptions::default(),
+        )
+        .await;
+
+        let outcomes = task.start().await.unwrap();
+
+        assert_eq!(
+            finalized(&outcomes),
+            vec![FinalizeOutcome::Approved {
+                reference: "3XK9QF".into()
+            }]
+        );
+        assert_eq!(outcomes[0].shipping.as_deref(), Some("Standard"));
+
+        let order = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(order.body["usePaymentIntent"], true);
+        assert_eq!(order.body["shippingMode"], "byMerchant");
+
+        let instrument = server
+            .wait_for(Method::POST, "gateway/instruments")
+            .await
+            .unwrap();
+        assert_eq!(instrument.body["cardNumber"], "4111111111111111");
+
+        let finalize = server
+            .wait_for(Method::POST, "checkout/v1/orders/1/finalize")
+            .await
+            .unwrap();
+        assert_eq!(finalize.body["paymentIntentId"], "pi_1");
+        assert_eq!(finalize.body["instrumentId"], "instrument_pi_1");
+        assert!(finalize.body.get("cardNumber").is_none());
+    }
+
+    #[tokio::test]
+    async fn sends_canonical_state() {
+        let mut profile = mock::profile(Country::GB);
+        profile.delivery.country = Country::US;
+        profile.delivery.state = Some("Calif.".into());
+        let server = MockServer::start().await;
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        task.start().await.unwrap();
+
+        let patch = server
+            .wait_for(Method::PATCH, "checkout/v1/orders/1")
+            .await
+            .unwrap();
+        assert_eq!(
+            patch.body["shippingAddress"]["state"],
+            json!({ "name": "California", "code": "CA" })
+        );
+        assert_eq!(
+            patch.body["billingAddress"]["state"],
+            json!({ "name": "", "code": "" })
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_preferred_size() {
+        let server = MockServer::start().await;
+        let mut profile = mock::profile(Country::GB);
+        profile.sizes = vec!["M".parse().unwrap(), "L".parse().unwrap()];
+        let (mut task, sender, mut release) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        let checkout = tokio::spawn(async move { task.start().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(server.count(Method::POST, "checkout/v1/orders"), 0);
+
+        release.variants[1].quantity = 2;
+        sender.send(release).unwrap();
+        checkout.await.unwrap().unwrap();
+
+        let order = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(
+            order.body["items"][0]["variantId"],
+            "b3f4a2de-0c3e-4f1a-9a53-7d0e3b0a1c02"
+        );
+    }
+
+    #[tokio::test]
+    async fn buys_a_scaled_size_in_the_product_scale() {
+        let server = MockServer::start().await;
+        let mut profile = mock::profile(Country::GB);
+        profile.sizes = vec!["US M".parse().unwrap(), "UK S".parse().unwrap()];
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        task.start().await.unwrap();
+
+        let order = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(
+            order.body["items"][0]["variantId"],
+            "b3f4a2de-0c3e-4f1a-9a53-7d0e3b0a1c01"
+        );
+    }
+
+    #[tokio::test]
+    async fn buys_a_scaled_size_in_a_configured_scale() {
+        let server = MockServer::with_scenario(Scenario::load("numeric_sizes")).await;
+        let mut profile = mock::profile(Country::GB);
+        profile.sizes = vec!["US 9".parse().unwrap(), "US 9.5".parse().unwrap()];
+        let options = TaskOptions {
+            scales: serde_json::from_value(json!([
+                { "id": 17, "category": "menShoes", "scale": "EU" }
+            ]))
+            .unwrap(),
+            ..TaskOptions::default()
+        };
+        let (mut task, _sender, _) = released(&server.storefront(), profile, options).await;
+
+        task.start().await.unwrap();
+
+        let order = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(
+            order.body["items"][0]["variantId"],
+            "c7e1d0b2-5a4f-4c3e-8b21-6f0a9d2e3b03"
+        );
+    }
+
+    #[tokio::test]
+    async fn waits_when_the_product_scale_is_unknown() {
+        let server = MockServer::with_scenario(Scenario::load("numeric_sizes")).await;
+        let mut profile = mock::profile(Country::GB);
+        profile.sizes = vec!["US 9.5".parse().unwrap()];
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        let checkout = tokio::spawn(async move { task.start().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        checkout.abort();
+
+        assert_eq!(server.count(Method::POST, "checkout/v1/orders"), 0);
+    }
+
+    #[tokio::test]
+    async fn aborts_when_order_is_in_another_currency() {
+        let server = MockServer::with_scenario(Scenario {
+            currency: Some("EUR".into()),
+            ..Scenario::default()
+        })
+        .await;
+        let storefront = server.storefront();
+        let (mut task, _sender, _) = released(
+            &storefront,
+            mock::profile(Country::GB),
+            TaskOptions::default(),
+        )
+        .await;
+        let state = task.state();
+
+        let why = task.start().await.unwrap_err();
+
+        assert_eq!(
+            why.to_string(),
+            "order_totals=order is in EUR, expected GBP"
+        );
+        assert!(matches!(*state.borrow(), CheckoutState::Failed { .. }));
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn aborts_when_shipping_exceeds_the_limit() {
+        let server = MockServer::start().await;
+        let storefront = server.storefront();
+        let options = TaskOptions {
+            price: PriceRules {
+                max_shipping: Some(10.0),
+                max_total: Some(405.0),
+                ..PriceRules::default()
+            },
+            ..TaskOptions::default()
+        };
+        let (mut task, _sender, _) =
+            released(&storefront, mock::profile(Country::GB), options).await;
+
+        let why = task.start().await.unwrap_err();
+
+        assert_eq!(
+            why.to_string(),
+            "order_totals=shipping 15 GBP is above maxShipping 10"
+        );
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_the_profile_shipping_option() {
+        let mut profile = mock::profile(Country::GB);
+        profile.shipping = ShippingPreference::Fastest;
+        let server = MockServer::start().await;
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        let outcomes = task.start().await.unwrap();
+
+        assert_eq!(outcomes[0].shipping.as_deref(), Some("Express"));
+        assert_eq!(
+            outcomes[0].to_string(),
+            "approved reference=3XK9QF shipping=\"Express\""
+        );
+
+        let shipping = server
+            .wait_for(Method::PUT, "checkout/v1/orders/1/shippingOptions")
+            .await
+            .unwrap();
+        assert_eq!(shipping.body[0]["shippingService"]["name"], "Express");
+        assert_eq!(shipping.body[0]["price"], 25.0);
+
+        let order = server
+            .wait_for(Method::POST, "checkout/v1/orders")
+            .await
+            .unwrap();
+        assert_eq!(order.body["shippingMode"], "byMerchant");
+
+        let requests = server.requests();
+        let position = |path: &str| {
+            requests
+                .iter()
+                .position(|request| request.path == path)
+                .unwrap()
+        };
+        assert!(
+            position("checkout/v1/orders/1/shippingOptions")
+                < position("checkout/v1/orders/1/finalize")
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_totals_with_the_chosen_shipping() {
+        let server = MockServer::start().await;
+        let storefront = server.storefront();
+        let mut profile = mock::profile(Country::GB);
+        profile.shipping = "express".parse().unwrap();
+        let options = TaskOptions {
+            price: PriceRules {
+                max_shipping: Some(20.0),
+                ..PriceRules::default()
+            },
+            ..TaskOptions::default()
+        };
+        let (mut task, _sender, _) = released(&storefront, profile, options).await;
+
+        let why = task.start().await.unwrap_err();
+
+        assert_eq!(
+            why.to_string(),
+            "order_totals=shipping 25 GBP is above maxShipping 20"
+        );
+        assert_eq!(
+            server.count(Method::POST, "checkout/v1/orders/1/finalize"),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_when_shipping_service_is_not_offered() {
+        let mut profile = mock::profile(Country::GB);
+        profile.shipping = "Same Day".parse().unwrap();
+        let server = MockServer::start().await;
+        let (mut task, _sender, _) =
+            released(&server.storefront(), profile, TaskOptions::default()).await;
+
+        let why = task.start().await.unwrap_err();
+
+        assert_eq!(
+            why.to_string(),
+            "shipping=Same Day is not offered, offered: Standard, Express"
+        );
+        assert_eq!(
+            server.count(Method::PUT, "checkout/v1/orders/1/shippingOptions"),
+            0
+        );
     }
 }
@@ -0,0 +1,298 @@
+//! Profiles encrypted at rest. The key is derived from a passphrase with Argon2id and the
+//! profiles are sealed with XChaCha20-Poly1305, so a vault can only be read, and only be
+//! changed without detection, by someone who knows the passphrase.
+//!
+//! Layout: `MAGIC`, 16 byte salt, 24 byte nonce, ciphertext of the JSON profiles.
+
+use crate::model::Profile;
+use crate::Error;
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, OsRng};
+use chacha20poly1305::{AeadCore, KeyInit, XChaCha20Poly1305, XNonce};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8] = b"EPVAULT1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Passphrase for the vault, read from `VAULT_PASSPHRASE` or asked for on the terminal
+/// without echoing it.
+pub fn passphrase() -> Result<Zeroizing<String>, Error> {
+    if let Ok(passphrase) = std::env::var("VAULT_PASSPHRASE") {
+        return Ok(Zeroizing::new(passphrase));
+    }
+
+    Ok(Zeroizing::new(rpassword::prompt_password(
+        "vault passphrase: ",
+    )?))
+}
+
+/// Passphrase for a new vault, asked for twice on the terminal so a typo does not lock the
+/// profiles away.
+fn new_passphrase() -> Result<Zeroizing<String>, Error> {
+    if let Ok(passphrase) = std::env::var("VAULT_PASSPHRASE") {
+        return Ok(Zeroizing::new(passphrase));
+    }
+
+    confirmed(|prompt| rpassword::prompt_password(prompt))
+}
+
+fn confirmed(mut ask: impl FnMut(&str) -> io::Result<String>) -> Result<Zeroizing<String>, Error> {
+    let passphrase = Zeroizing::new(ask("new vault passphrase: ")?);
+    let repeated = Zeroizing::new(ask("repeat passphrase: ")?);
+
+    if passphrase.is_empty() {
+        Err(Error::Vault("passphrase must not be empty".into()))
+    } else if passphrase != repeated {
+        Err(Error::Vault("passphrases do not match".into()))
+    } else {
+        Ok(passphrase)
+    }
+}
+
+/// Profiles by alias, decrypted in memory only.
+#[derive(Debug, Default)]
+pub struct Vault {
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Vault {
+    pub fn open(path: &Path, passphrase: &str) -> Result<Vault, Error> {
+        let sealed =
+            fs::read(path).map_err(|why| Error::Vault(format!("{}: {}", path.display(), why)))?;
+        let json = unseal(&sealed, passphrase)?;
+        let profiles = serde_json::from_slice(&json)?;
+
+        Ok(Vault { profiles })
+    }
+
+    pub fn save(&self, path: &Path, passphrase: &str) -> Result<(), Error> {
+        let json = Zeroizing::new(serde_json::to_vec(&self.profiles)?);
+
+        let sealed = seal(&json, passphrase)?;
+
+        // Written next to the vault and renamed over it, so a failed write leaves the
+        // previous vault intact.
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let written = fs::File::create(&temp)
+            .and_then(|mut file| {
+                file.write_all(&sealed)?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp, path));
+
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+
+        Ok(written?)
+    }
+
+    /// A vault holding `profiles` unchecked, as if imported before they went stale.
+    #[cfg(test)]
+    pub fn unchecked(profiles: BTreeMap<String, Profile>) -> Vault {
+        Vault { profiles }
+    }
+
+    pub fn profile(&self, alias: &str) -> Option<&Profile> {
+        self.profiles.get(alias)
+    }
+
+    pub fn profiles(&self) -> impl Iterator<Item = (&String, &Profile)> {
+        self.profiles.iter()
+    }
+
+    /// Adds `profiles`, replacing those with the same alias. Nothing is added unless every
+    /// profile is valid.
+    pub fn import(&mut self, mut profiles: BTreeMap<String, Profile>) -> Result<(), Error> {
+        let problems = profiles
+            .iter_mut()
+            .flat_map(|(alias, profile)| {
+                profile
+                    .validate()
+                    .into_iter()
+                    .map(move |problem| format!("profile={} {}", alias, problem))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if !problems.is_empty() {
+            return Err(Error::InvalidConfig(problems.join("; ")));
+        }
+
+        self.profiles.extend(profiles);
+
+        Ok(())
+    }
+}
+
+/// `vault import <vault> <profiles.json>`: adds the plaintext profiles, keyed by alias, to
+/// the vault, creating it if needed.
+pub fn import(vault: &Path, profiles: &Path) -> Result<(), Error> {
+    let (mut store, passphrase) = if vault.exists() {
+        let passphrase = passphrase()?;
+        (Vault::open(vault, &passphrase)?, passphrase)
+    } else {
+        (Vault::default(), new_passphrase()?)
+    };
+
+    let file = fs::File::open(profiles)?;
+    let profiles: BTreeMap<String, Profile> = serde_json::from_reader(file)?;
+    let count = profiles.len();
+
+    store.import(profiles)?;
+    store.save(vault, &passphrase)?;
+    println!("imported {} profiles into {}", count, vault.display());
+
+    Ok(())
+}
+
+/// `vault list <vault>`: prints the aliases with just enough to tell profiles apart.
+pub fn list(vault: &Path) -> Result<(), Error> {
+    let store = Vault::open(vault, &passphrase()?)?;
+
+    for (alias, profile) in store.profiles() {
+        let card = &profile.card;
+        let brand = card.brand().map(|brand| brand.to_string());
+
+        println!(
+            "{}\t{}\t{} {}",
+            alias,
+            profile.delivery.country.data().fps.name,
+            brand.unwrap_or_default(),
+            card.number
+        );
+    }
+
+    Ok(())
+}
+
+fn key(passphrase: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, Error> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .map_err(|why| Error::Vault(why.to_string()))?;
+
+    Ok(key)
+}
+
+fn seal(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&*key(passphrase, &salt)?)
+        .map_err(|why| Error::Vault(why.to_string()))?;
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::Vault("encryption failed".into()))?;
+
+    Ok([MAGIC, &salt, &nonce, &ciphertext].concat())
+}
+
+fn unseal(sealed: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let header = MAGIC.len() + SALT_LEN + NONCE_LEN;
+    if sealed.len() < header || !sealed.starts_with(MAGIC) {
+        return Err(Error::Vault("not a profile vault".into()));
+    }
+
+    let salt = &sealed[MAGIC.len()..MAGIC.len() + SALT_LEN];
+    let nonce = XNonce::from_slice(&sealed[MAGIC.len() + SALT_LEN..header]);
+
+    let cipher = XChaCha20Poly1305::new_from_slice(&*key(passphrase, salt)?)
+        .map_err(|why| Error::Vault(why.to_string()))?;
+    let plaintext = cipher
+        .decrypt(nonce, &sealed[header..])
+        .map_err(|_| Error::Vault("wrong passphrase or corrupted vault".into()))?;
+
+    Ok(Zeroizing::new(plaintext))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::country::Country;
+    use crate::mock;
+
+    #[test]
+    fn unseals_with_the_same_passphrase_only() {
+        let sealed = seal(b"{}", "correct horse").unwrap();
+
+        assert_eq!(&*unseal(&sealed, "correct horse").unwrap(), b"{}");
+        assert!(matches!(
+            unseal(&sealed, "battery staple"),
+            Err(Error::Vault(why)) if why == "wrong passphrase or corrupted vault"
+        ));
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(unseal(&tampered, "correct horse").is_err());
+        assert!(unseal(b"{\"plain\": true}", "correct horse").is_err());
+    }
+
+    #[test]
+    fn saves_and_opens_profiles() {
+        let path = std::env::temp_dir().join(format!("vault-{}.bin", std::process::id()));
+        let mut vault = Vault::default();
+        let mut profile = mock::profile(Country::GB);
+        profile.card.number = "4111 1111 1111 1111".into();
+
+        vault
+            .import(BTreeMap::from([("jane".to_string(), profile)]))
+            .unwrap();
+        vault.save(&path, "passphrase").unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&bytes).contains("4111"));
+
+        vault.save(&path, "changed").unwrap();
+        assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+
+        let opened = Vault::open(&path, "changed").unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let profile = opened.profile("jane").unwrap();
+        assert_eq!(profile.card.number.expose(), "4111111111111111");
+        assert_eq!(opened.profiles().count(), 1);
+    }
+
+    #[test]
+    fn confirms_new_passphrases() {
+        let answers = |answers: &'static [&'static str]| {
+            let mut answers = answers.iter();
+            move |_: &str| Ok(answers.next().unwrap().to_string())
+        };
+
+        assert_eq!(
+            &*confirmed(answers(&["correct horse", "correct horse"])).unwrap(),
+            "correct horse"
+        );
+        assert!(matches!(
+            confirmed(answers(&["correct horse", "correct hrose"])),
+            Err(Error::Vault(why)) if why == "passphrases do not match"
+        ));
+        assert!(confirmed(answers(&["", ""])).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        let mut vault = Vault::default();
+        let mut profile = mock::profile(Country::GB);
+        profile.card.expiry_year = 2001;
+
+        let why = vault
+            .import(BTreeMap::from([("jane".to_string(), profile)]))
+            .unwrap_err();
+
+        assert!(why
+            .to_string()
+            .contains("profile=jane card expired in 2001-12"));
+        assert_eq!(vault.profiles().count(), 0);
+    }
+}